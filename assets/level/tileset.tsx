@@ -1,5 +1,5 @@
 <?xml version="1.0" encoding="UTF-8"?>
-<tileset version="1.9" tiledversion="1.9.1" name="tileset" tilewidth="50" tileheight="50" tilecount="47" columns="0">
+<tileset version="1.9" tiledversion="1.9.1" name="tileset" tilewidth="50" tileheight="50" tilecount="49" columns="0">
  <grid orientation="orthogonal" width="1" height="1"/>
  <tile id="2">
   <image width="50" height="50" source="../img/player.png"/>
@@ -26,6 +26,9 @@
   <image width="50" height="50" source="../img/carboard_box_knife.png"/>
  </tile>
  <tile id="12">
+  <properties>
+   <property name="station" value="table"/>
+  </properties>
   <image width="50" height="50" source="../img/table.png"/>
  </tile>
  <tile id="13">
@@ -74,6 +77,10 @@
   <image width="50" height="50" source="../img/door.png"/>
  </tile>
  <tile id="28">
+  <properties>
+   <property name="pickup_item" value="shells"/>
+   <property name="pickup_quantity" type="int" value="8"/>
+  </properties>
   <image width="50" height="50" source="../img/shells.png"/>
  </tile>
  <tile id="29">
@@ -142,4 +149,26 @@
  <tile id="51">
   <image width="50" height="50" source="../img/fun_dust.png"/>
  </tile>
+ <tile id="52">
+  <image width="50" height="50" source="../img/tp.png"/>
+ </tile>
+ <tile id="53">
+  <properties>
+   <property name="pickup_item" value="medkit"/>
+  </properties>
+  <image width="50" height="50" source="../img/medkit.png"/>
+ </tile>
+ <tile id="54">
+  <properties>
+   <property name="light_intensity" type="float" value="1"/>
+   <property name="light_radius" type="float" value="5"/>
+  </properties>
+  <image width="50" height="50" source="../img/lamp.png"/>
+ </tile>
+ <tile id="55">
+  <properties>
+   <property name="pickup_armor" type="float" value="50"/>
+  </properties>
+  <image width="50" height="50" source="../img/armor.png"/>
+ </tile>
 </tileset>
@@ -8,18 +8,20 @@ var texture: texture_2d<f32>;
 @group(1) @binding(1)
 var our_sampler: sampler;
 
-struct Time {
-    time: u32,
-    padding_1: u32,
-    padding_2: u32,
-    padding_3: u32,
+struct ShaderParams {
+    // In milliseconds
+    time: f32,
+    // How much of the effect gets mixed into the image
+    intensity: f32,
+    // In radians
+    hue_shift: f32,
+    distortion: f32,
 }
 
 @group(1) @binding(2)
-var<uniform> time: Time;
+var<uniform> params: ShaderParams;
 
 // sume constants
-let screen_shape_factor:f32 = .5;
 let rows: f32 = 256.0;
 let brightness: f32 = 3.0;
 let edges_transition_size: f32 = 0.2;
@@ -142,10 +144,11 @@ fn fragment(
     @builtin(position) position: vec4<f32>,
     #import bevy_sprite::mesh2d_vertex_output
 ) -> @location(0) vec4<f32> {
-    let time = f32(time.time);
+    let time = params.time;
 
+    let original = get_texture_color(get_uv(position.xy));
     let uv = get_uv(position.xy);
-    let uv = apply_screen_shape(uv, screen_shape_factor);
+    let uv = apply_screen_shape(uv, params.distortion);
 
     let cols = rows * view.width / view.height;
 
@@ -164,7 +167,7 @@ fn fragment(
         * cool_sine(uv.x + 100.0, (-time)*0.0005)
         * cool_sine(uv.y, (time)*0.000316);
 
-    let color = adjust_hue(color, hue*2.);
+    let color = adjust_hue(color, hue*2. + params.hue_shift);
 
-    return color;
+    return mix(original, color, params.intensity);
 }
\ No newline at end of file
@@ -0,0 +1,74 @@
+#import bevy_pbr::mesh_view_bindings
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var our_sampler: sampler;
+
+struct Colorblind {
+    // 1: protanopia, 2: deuteranopia, 3: tritanopia
+    mode: u32,
+    padding_0: u32,
+    padding_1: u32,
+    padding_2: u32,
+}
+
+@group(1) @binding(2)
+var<uniform> colorblind: Colorblind;
+
+// Daltonization: simulate what's seen in LMS space and move the lost difference into the channels that are still there
+@fragment
+fn fragment(
+    @builtin(position) position: vec4<f32>,
+    #import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+    // Get screen position with coordinates from 0 to 1
+    let uv = position.xy / vec2<f32>(view.width, view.height);
+    let color = textureSample(texture, our_sampler, uv);
+
+    let rgb_to_lms = mat3x3<f32>(
+        vec3<f32>(17.8824, 3.45565, 0.0299566),
+        vec3<f32>(43.5161, 27.1554, 0.184309),
+        vec3<f32>(4.11935, 3.86714, 1.46709),
+    );
+    let lms_to_rgb = mat3x3<f32>(
+        vec3<f32>(0.0809444479, -0.0102485335, -0.000365296938),
+        vec3<f32>(-0.130504409, 0.0540193266, -0.00412161469),
+        vec3<f32>(0.116721066, -0.113614708, 0.693511405),
+    );
+
+    var simulation = mat3x3<f32>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+    );
+
+    if (colorblind.mode == 1u) {
+        simulation = mat3x3<f32>(
+            vec3<f32>(0.0, 0.0, 0.0),
+            vec3<f32>(2.02344, 1.0, 0.0),
+            vec3<f32>(-2.52581, 0.0, 1.0),
+        );
+    } else if (colorblind.mode == 2u) {
+        simulation = mat3x3<f32>(
+            vec3<f32>(1.0, 0.494207, 0.0),
+            vec3<f32>(0.0, 0.0, 0.0),
+            vec3<f32>(0.0, 1.24827, 1.0),
+        );
+    } else if (colorblind.mode == 3u) {
+        simulation = mat3x3<f32>(
+            vec3<f32>(1.0, 0.0, -0.395913),
+            vec3<f32>(0.0, 1.0, 0.801109),
+            vec3<f32>(0.0, 0.0, 0.0),
+        );
+    }
+
+    let seen = lms_to_rgb * (simulation * (rgb_to_lms * color.rgb));
+    let lost = color.rgb - seen;
+
+    // Shift what couldn't be seen towards green and blue
+    let shift = vec3<f32>(0.0, 0.7 * lost.r + lost.g, 0.7 * lost.r + lost.b);
+
+    return vec4<f32>(clamp(color.rgb + shift, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
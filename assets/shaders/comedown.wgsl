@@ -0,0 +1,52 @@
+#import bevy_pbr::mesh_view_bindings
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var our_sampler: sampler;
+
+struct Comedown {
+    time: u32,
+    // From 0 to 100, an overdose is 100
+    intensity: u32,
+    padding_1: u32,
+    padding_2: u32,
+}
+
+@group(1) @binding(2)
+var<uniform> comedown: Comedown;
+
+@fragment
+fn fragment(
+    @builtin(position) position: vec4<f32>,
+    #import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+    let time = f32(comedown.time);
+    let intensity = f32(comedown.intensity) / 100.0;
+    // Get screen position with coordinates from 0 to 1
+    let uv = position.xy / vec2<f32>(view.width, view.height);
+
+    // The blur slowly breathes in and out
+    let radius = intensity * (0.003 + 0.002 * sin(time / 600.0));
+
+    var blurred = vec4<f32>(0.0);
+    for (var x: i32 = -2; x <= 2; x = x + 1) {
+        for (var y: i32 = -2; y <= 2; y = y + 1) {
+            blurred = blurred + textureSample(texture, our_sampler, uv + vec2<f32>(f32(x), f32(y)) * radius);
+        }
+    }
+    blurred = blurred / 25.0;
+
+    // Drain the colors out
+    let grey = dot(blurred.rgb, vec3<f32>(0.299, 0.587, 0.114));
+    var color = mix(blurred.rgb, vec3<f32>(grey), 0.6 * intensity);
+
+    // Overdosing pulses red around the edges of the screen
+    let overdose = max(intensity - 0.5, 0.0) * 2.0;
+    let vignette = smoothstep(0.3, 0.8, length(uv - vec2<f32>(0.5)));
+    let pulse = 0.5 + 0.5 * sin(time / 150.0);
+    color = mix(color, vec3<f32>(0.8, 0.0, 0.0), vignette * pulse * overdose);
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,38 @@
+#import bevy_pbr::mesh_view_bindings
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var our_sampler: sampler;
+
+struct DamageFlash {
+    // From 0 to 100, fades out after the hit
+    intensity: u32,
+    padding_0: u32,
+    padding_1: u32,
+    padding_2: u32,
+}
+
+@group(1) @binding(2)
+var<uniform> flash: DamageFlash;
+
+@fragment
+fn fragment(
+    @builtin(position) position: vec4<f32>,
+    #import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+    let intensity = f32(flash.intensity) / 100.0;
+    // Get screen position with coordinates from 0 to 1
+    let uv = position.xy / vec2<f32>(view.width, view.height);
+
+    // The screen gets knocked apart a little, splitting the red channel off
+    let offset = vec2<f32>(0.006 * intensity, 0.0);
+    let red = textureSample(texture, our_sampler, uv + offset).r;
+    let color = textureSample(texture, our_sampler, uv);
+
+    let shifted = vec3<f32>(red, color.g, color.b);
+    let tinted = mix(shifted, vec3<f32>(1.0, 0.1, 0.1), 0.35 * intensity);
+
+    return vec4<f32>(tinted, 1.0);
+}
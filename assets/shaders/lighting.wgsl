@@ -0,0 +1,100 @@
+#import bevy_pbr::mesh_view_bindings
+
+struct Light {
+    // xy: position in the world, z: radius
+    position_radius: vec4<f32>,
+    // rgb: color, a: intensity
+    color_intensity: vec4<f32>,
+};
+
+struct Lighting {
+    ambient: vec4<f32>,
+    // xy: world position of the bottom left corner of the occlusion map, zw: its size in tiles
+    occlusion_area: vec4<f32>,
+    // x: number of lights, y: size of a tile
+    info: vec4<f32>,
+    // xy: world position of the center of the screen, zw: size of the screen in the world
+    view_area: vec4<f32>,
+    lights: array<Light, 16>,
+};
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var our_sampler: sampler;
+
+@group(1) @binding(2)
+var<uniform> lighting: Lighting;
+
+// One texel per tile, red is 1 where there's a wall
+@group(1) @binding(3)
+var occlusion_map: texture_2d<f32>;
+
+fn tile_of(point: vec2<f32>) -> vec2<i32> {
+    return vec2<i32>(floor((point - lighting.occlusion_area.xy) / lighting.info.y));
+}
+
+fn is_wall(tile: vec2<i32>) -> bool {
+    let size = vec2<i32>(lighting.occlusion_area.zw);
+
+    if (tile.x < 0 || tile.y < 0 || tile.x >= size.x || tile.y >= size.y) {
+        return false;
+    }
+
+    return textureLoad(occlusion_map, tile, 0).r > 0.5;
+}
+
+@fragment
+fn fragment(
+    @builtin(position) position: vec4<f32>,
+    #import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+    // Get screen position with coordinates from 0 to 1
+    let uv = position.xy / vec2<f32>(view.width, view.height);
+    let color = textureSample(texture, our_sampler, uv);
+
+    // The screen goes down from its top, the world goes up
+    let point = lighting.view_area.xy + (uv - vec2<f32>(0.5)) * vec2<f32>(1.0, -1.0) * lighting.view_area.zw;
+    let own_tile = tile_of(point);
+    // Four samples per tile are enough to not skip the corners of the walls
+    let step_size = lighting.info.y * 0.25;
+
+    var light = lighting.ambient.rgb;
+
+    for (var i = 0; i < i32(lighting.info.x); i = i + 1) {
+        let current = lighting.lights[i];
+        let to_light = current.position_radius.xy - point;
+        let light_distance = length(to_light);
+        let radius = current.position_radius.z;
+
+        if (light_distance >= radius) {
+            continue;
+        }
+
+        let light_tile = tile_of(current.position_radius.xy);
+        let steps = i32(ceil(light_distance / step_size));
+        var lit = true;
+
+        // Walls are lit from the side facing the light, so their own tile doesn't block it
+        for (var n = 1; n < steps; n = n + 1) {
+            let tile = tile_of(point + to_light * (f32(n) / f32(steps)));
+
+            if (all(tile == own_tile) || all(tile == light_tile)) {
+                continue;
+            }
+
+            if (is_wall(tile)) {
+                lit = false;
+                break;
+            }
+        }
+
+        if (lit) {
+            let falloff = 1.0 - light_distance / radius;
+            light = light + current.color_intensity.rgb * current.color_intensity.a * falloff * falloff;
+        }
+    }
+
+    return vec4<f32>(color.rgb * min(light, vec3<f32>(1.0, 1.0, 1.0)), 1.0);
+}
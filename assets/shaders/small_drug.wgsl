@@ -6,15 +6,18 @@ var texture: texture_2d<f32>;
 @group(1) @binding(1)
 var our_sampler: sampler;
 
-struct Time {
-    time: u32,
-    padding_1: u32,
-    padding_2: u32,
-    padding_3: u32,
+struct ShaderParams {
+    // In milliseconds
+    time: f32,
+    // How much of the effect gets mixed into the image
+    intensity: f32,
+    // In radians
+    hue_shift: f32,
+    distortion: f32,
 }
 
 @group(1) @binding(2)
-var<uniform> time: Time;
+var<uniform> params: ShaderParams;
 
 fn adjust_hue(color: vec4<f32>, hueAdjust: f32) -> vec4<f32> {
     let kRGBToYPrime: vec4<f32> = vec4<f32>(0.299, 0.587, 0.114, 0.0);
@@ -73,11 +76,11 @@ fn fragment(
     @builtin(position) position: vec4<f32>,
     #import bevy_sprite::mesh2d_vertex_output
 ) -> @location(0) vec4<f32> {
-    let time = f32(time.time);
+    let time = params.time;
     // Get screen position with coordinates from 0 to 1
     let uv = (position.xy / vec2<f32>(view.width, view.height));
-    let offset_strength = 0.002;
-    let time_offset = sin(time / 100.0) / 100.0  * noise(uv * 10.0 + time / 1000.0);
+    let offset_strength = 0.002 * params.distortion;
+    let time_offset = sin(time / 100.0) / 100.0  * noise(uv * 10.0 + time / 1000.0) * params.distortion;
 
     // Sample each color channel with an arbitrary shift
     var output_color1 = vec4<f32>(
@@ -87,7 +90,7 @@ fn fragment(
         1.0
         );
 
-    var output_color: vec4<f32> = adjust_hue(output_color1, 123.0);
+    var output_color: vec4<f32> = adjust_hue(output_color1, params.hue_shift);
 
-    return output_color;
+    return mix(textureSample(texture, our_sampler, uv), output_color, params.intensity);
 }
\ No newline at end of file
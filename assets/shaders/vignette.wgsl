@@ -0,0 +1,35 @@
+#import bevy_pbr::mesh_view_bindings
+
+@group(1) @binding(0)
+var texture: texture_2d<f32>;
+
+@group(1) @binding(1)
+var our_sampler: sampler;
+
+struct Vignette {
+    // From 0 to 100, the lower the health the stronger it gets
+    intensity: u32,
+    padding_0: u32,
+    padding_1: u32,
+    padding_2: u32,
+}
+
+@group(1) @binding(2)
+var<uniform> vignette: Vignette;
+
+@fragment
+fn fragment(
+    @builtin(position) position: vec4<f32>,
+    #import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+    let intensity = f32(vignette.intensity) / 100.0;
+    // Get screen position with coordinates from 0 to 1
+    let uv = position.xy / vec2<f32>(view.width, view.height);
+    let color = textureSample(texture, our_sampler, uv);
+
+    // The edges close in as the health goes down
+    let edge = smoothstep(0.75 - 0.35 * intensity, 0.9, length(uv - vec2<f32>(0.5)) * 1.4);
+    let darkened = mix(color.rgb, vec3<f32>(0.25, 0.0, 0.0), edge * (0.4 + 0.5 * intensity));
+
+    return vec4<f32>(darkened, 1.0);
+}
@@ -6,8 +6,18 @@ var texture: texture_2d<f32>;
 @group(1) @binding(1)
 var our_sampler: sampler;
 
+struct ShaderParams {
+    // In milliseconds
+    time: f32,
+    // How much of the effect gets mixed into the image
+    intensity: f32,
+    // In radians
+    hue_shift: f32,
+    distortion: f32,
+}
+
 @group(1) @binding(2)
-var<uniform> time: u32;
+var<uniform> params: ShaderParams;
 
 fn adjust_hue(color: vec4<f32>, hueAdjust: f32) -> vec4<f32> {
     let kRGBToYPrime: vec4<f32> = vec4<f32>(0.299, 0.587, 0.114, 0.0);
@@ -46,14 +56,13 @@ fn fragment(
     @builtin(position) position: vec4<f32>,
     #import bevy_sprite::mesh2d_vertex_output
 ) -> @location(0) vec4<f32> {
-    let time = f32(time);
+    let time = params.time;
     // Get screen position with coordinates from 0 to 1
     let uv = position.xy / vec2<f32>(view.width, view.height);
+    let wave = vec2<f32>(sin(uv.y * 20.0 + time / 300.0), cos(uv.x * 20.0 + time / 300.0)) * 0.01 * params.distortion;
 
-    // Sample each color channel with an arbitrary shift
-    var output_color1 = vec4<f32>(textureSample(texture, our_sampler, uv));
-
-    var output_color = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    let color = adjust_hue(textureSample(texture, our_sampler, uv + wave), params.hue_shift);
 
-    return output_color;
+    // Black at zero intensity
+    return mix(vec4<f32>(0.0, 0.0, 0.0, 1.0), color, params.intensity);
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
-use bevy_kira_audio::{AudioSource, Audio, AudioControl};
+use bevy_kira_audio::{AudioSource, Audio, AudioControl, DynamicAudioChannels};
 
+use crate::audio_player::world_channels;
 use crate::time::TimeCounter;
 
 #[derive(Deref, DerefMut)]
@@ -21,6 +22,17 @@ pub struct SnortingSounds(pub Vec<Handle<AudioSource>>);
 #[derive(Deref, DerefMut)]
 pub struct CraftingSound(pub Handle<AudioSource>);
 
+#[derive(Deref, DerefMut)]
+pub struct PickupSound(pub Handle<AudioSource>);
+
+#[derive(Deref, DerefMut)]
+pub struct HeartbeatSound(pub Handle<AudioSource>);
+
+#[derive(Deref, DerefMut)]
+pub struct Music(pub Handle<AudioSource>);
+
+const MIN_PLAYBACK_RATE: f32 = 0.1;
+
 pub struct AudioLoadPlugin;
 
 impl Plugin for AudioLoadPlugin {
@@ -55,8 +67,22 @@ fn load_audio(mut commands: Commands, asset_server: Res<AssetServer>) {
     ]));
 
     commands.insert_resource(CraftingSound(asset_server.load("./audio/craft_drug.wav")));
+    commands.insert_resource(PickupSound(asset_server.load("./audio/pickup.wav")));
+    commands.insert_resource(HeartbeatSound(asset_server.load("./audio/heartbeat.wav")));
+    commands.insert_resource(Music(asset_server.load("./audio/song.wav")));
 }
 
-fn update_playback_rate(audio: Res<Audio>, time: Res<TimeCounter>) {
-    audio.set_playback_rate(time.timescale as f64);
+fn update_playback_rate(audio: Res<Audio>, channels: Res<DynamicAudioChannels>, time: Res<TimeCounter>) {
+    // The pause menu pauses the audio instead, and kira can't play at a rate of zero
+    if time.is_paused() {
+        return;
+    }
+
+    let rate = time.timescale().max(MIN_PLAYBACK_RATE) as f64;
+
+    audio.set_playback_rate(rate);
+
+    for channel in world_channels(&channels) {
+        channel.set_playback_rate(rate);
+    }
 }
@@ -1,28 +1,412 @@
 use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioSource, AudioControl};
+use bevy_inspector_egui::Inspectable;
+use bevy_kira_audio::{
+	Audio, AudioControl, AudioSource, DynamicAudioChannel, DynamicAudioChannels, InstanceHandle, PlaybackState,
+};
+use rand::seq::SliceRandom;
 
+use crate::audio::{
+	CraftingSound, EnemyShotSound, FootstepSounds, HeartbeatSound, Music, PickupSound, Screams,
+	ShotgunSound, SnortingSounds,
+};
+use crate::camera::Viewport;
+use crate::player::Player;
+use crate::post_processing::MainCamera;
+use crate::rng::GameRng;
 use crate::settings::Settings;
+use crate::TILE_SIZE;
 
-pub const MUSIC_VOLUME: f64 = 0.025;
-pub const PLAYER_SHOT_VOLUME: f64 = 0.05;
-pub const PLAYER_FOOTSTEP_VOLUME: f64 = 1.0;
-pub const PLAYER_SNORTING_VOLUME: f64 = 0.1;
-pub const PLAYER_CRAFTING_VOLUME: f64 = 0.1;
-pub const ENEMY_SHOT_VOLUME: f64 = 0.1;
-pub const ENEMY_DEATH_SCREAM_VOLUME: f64 = 0.3;
+const MUSIC_VOLUME: f64 = 0.025;
+const PLAYER_SHOT_VOLUME: f64 = 0.05;
+const PLAYER_FOOTSTEP_VOLUME: f64 = 1.0;
+const PLAYER_SNORTING_VOLUME: f64 = 0.1;
+const PLAYER_CRAFTING_VOLUME: f64 = 0.1;
+const PLAYER_PICKUP_VOLUME: f64 = 0.1;
+const PLAYER_HEARTBEAT_VOLUME: f64 = 0.4;
+const ENEMY_SHOT_VOLUME: f64 = 0.1;
+const ENEMY_DEATH_SCREAM_VOLUME: f64 = 0.3;
 
-pub struct AudioPlayer;
+/// Where the song loops back to once it ends, in seconds
+const MUSIC_LOOP_START: f64 = 9.54;
 
-impl AudioPlayer {
-	pub fn play_sfx(audio: &Audio, sound: Handle<AudioSource>, default_volume: f64, settings: &Settings) {
-		audio.play(sound).with_volume(settings.sfx_volume * default_volume);
+/// Sounds playing at once, past that only the more important ones get to play
+const MAX_VOICES: usize = 24;
+
+/// Every sound that comes from somewhere in the world gets a channel of its own,
+/// so it can be panned and faded without affecting the others
+const WORLD_CHANNELS: usize = 12;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SoundId {
+	Shotgun,
+	EnemyShot,
+	Footstep,
+	Scream,
+	Snorting,
+	Crafting,
+	Pickup,
+	Heartbeat,
+	Music,
+}
+
+impl SoundId {
+	fn volume(self) -> f64 {
+		match self {
+			SoundId::Shotgun => PLAYER_SHOT_VOLUME,
+			SoundId::EnemyShot => ENEMY_SHOT_VOLUME,
+			SoundId::Footstep => PLAYER_FOOTSTEP_VOLUME,
+			SoundId::Scream => ENEMY_DEATH_SCREAM_VOLUME,
+			SoundId::Snorting => PLAYER_SNORTING_VOLUME,
+			SoundId::Crafting => PLAYER_CRAFTING_VOLUME,
+			SoundId::Pickup => PLAYER_PICKUP_VOLUME,
+			SoundId::Heartbeat => PLAYER_HEARTBEAT_VOLUME,
+			SoundId::Music => MUSIC_VOLUME,
+		}
+	}
+
+	/// How many of the sound can play at once, stacking more of them only makes it clip.
+	fn max_voices(self) -> usize {
+		match self {
+			SoundId::Shotgun | SoundId::EnemyShot => 3,
+			SoundId::Footstep => 2,
+			SoundId::Heartbeat | SoundId::Music => 1,
+			SoundId::Scream | SoundId::Snorting | SoundId::Crafting | SoundId::Pickup => 4,
+		}
+	}
+}
+
+/// Which of the volumes in the settings the sound follows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SoundCategory {
+	Sfx,
+	Music,
+}
+
+impl SoundCategory {
+	fn volume(self, settings: &Settings) -> f64 {
+		match self {
+			SoundCategory::Sfx => settings.sfx_volume,
+			SoundCategory::Music => settings.music_volume,
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum SoundPriority {
+	Low,
+	Normal,
+	High,
+}
+
+impl SoundPriority {
+	/// The less important sounds leave some of the voices free for the more important ones.
+	fn max_voices(self) -> usize {
+		match self {
+			SoundPriority::Low => MAX_VOICES / 2,
+			SoundPriority::Normal => MAX_VOICES * 3 / 4,
+			SoundPriority::High => MAX_VOICES,
+		}
+	}
+}
+
+/// Asks for a sound to be played, the audio player picks the handle and the volume.
+#[derive(Clone, Copy, Debug)]
+pub struct PlaySound {
+	pub sound: SoundId,
+	/// Where in the world the sound comes from, None if it's not anywhere in particular
+	pub position: Option<Vec2>,
+	pub category: SoundCategory,
+	pub priority: SoundPriority,
+	/// On top of the volume of the sound and its category
+	pub volume: f64,
+}
+
+impl PlaySound {
+	pub fn new(sound: SoundId) -> Self {
+		Self {
+			sound,
+			position: None,
+			category: SoundCategory::Sfx,
+			priority: SoundPriority::Normal,
+			volume: 1.0,
+		}
+	}
+
+	pub fn at(mut self, position: Vec2) -> Self {
+		self.position = Some(position);
+		self
 	}
 
-	pub fn play_music(audio: &Audio, sound: Handle<AudioSource>, default_volume: f64, settings: &Settings, loop_start: f64) {
-		audio.play(sound).with_volume(settings.music_volume * default_volume).loop_from(loop_start);
+	pub fn in_category(mut self, category: SoundCategory) -> Self {
+		self.category = category;
+		self
 	}
 
-	pub fn stop(audio: &Audio) {
-		audio.stop();
+	pub fn with_priority(mut self, priority: SoundPriority) -> Self {
+		self.priority = priority;
+		self
+	}
+
+	pub fn with_volume(mut self, volume: f64) -> Self {
+		self.volume = volume;
+		self
+	}
+}
+
+/// How world sounds fade out and move between the ears the further they are from the listener.
+#[derive(Inspectable)]
+pub struct SoundFalloff {
+	/// Sounds closer than this play at their full volume
+	#[inspectable(min = 0.0, speed = 1.0)]
+	pub full_volume_distance: f32,
+	/// Sounds further than this can't be heard at all
+	#[inspectable(min = 0.0, speed = 1.0)]
+	pub silent_distance: f32,
+	/// The shape of the fade in between, 1 fades out evenly and higher values fade out sooner
+	#[inspectable(min = 0.1, max = 5.0, speed = 0.01)]
+	pub curve: f32,
+	/// Zero keeps every sound in the middle, one lets them go all the way into one ear
+	#[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+	pub pan_strength: f32,
+}
+
+impl Default for SoundFalloff {
+	fn default() -> Self {
+		Self {
+			full_volume_distance: TILE_SIZE * 4.0,
+			silent_distance: TILE_SIZE * 24.0,
+			curve: 2.0,
+			pan_strength: 0.8,
+		}
+	}
+}
+
+impl SoundFalloff {
+	fn volume(&self, distance: f32) -> f64 {
+		let range = (self.silent_distance - self.full_volume_distance).max(f32::EPSILON);
+		let fade = ((distance - self.full_volume_distance) / range).clamp(0.0, 1.0);
+
+		(1.0 - fade).powf(self.curve) as f64
+	}
+
+	/// Measured like kira does it, zero is only the left ear, one only the right one and a half is the middle.
+	/// Sounds at the edge of the screen, or further, are panned all the way.
+	fn panning(&self, horizontal_offset: f32, viewport: &Viewport) -> f64 {
+		let full_pan_distance = (viewport.size.x / 2.0).max(1.0);
+		let side = (horizontal_offset / full_pan_distance).clamp(-1.0, 1.0);
+
+		(0.5 + side * self.pan_strength * 0.5) as f64
+	}
+}
+
+/// Where world sounds are heard from, the player or the camera when there's no player.
+#[derive(Default)]
+struct AudioListener(Option<Vec2>);
+
+struct Voice {
+	sound: SoundId,
+	instance: InstanceHandle,
+	/// The volume of the sound, its category and the request, before the falloff
+	volume: f64,
+	/// The world channel it plays on and where it comes from, None for sounds on the main channel
+	world: Option<(usize, Vec2)>,
+}
+
+/// The sounds that are still playing.
+#[derive(Default)]
+struct Voices(Vec<Voice>);
+
+impl Voices {
+	fn playing(&self, sound: SoundId) -> usize {
+		self.0.iter().filter(|voice| voice.sound == sound).count()
+	}
+
+	fn free_world_channel(&self) -> Option<usize> {
+		(0..WORLD_CHANNELS).find(|channel| {
+			!self.0.iter().any(|voice| voice.world.map_or(false, |(used, _)| used == *channel))
+		})
+	}
+}
+
+fn world_channel_key(index: usize) -> String {
+	format!("world_sound_{}", index)
+}
+
+fn world_channel(channels: &DynamicAudioChannels, index: usize) -> &DynamicAudioChannel {
+	channels.channel(&world_channel_key(index))
+}
+
+/// The channels the world sounds play on, everything done to the main channel has to be done to them too.
+pub fn world_channels(channels: &DynamicAudioChannels) -> impl Iterator<Item = &DynamicAudioChannel> {
+	(0..WORLD_CHANNELS).map(move |index| world_channel(channels, index))
+}
+
+/// Pans and fades the channel of a world sound for where it is compared to the listener.
+///
+/// The sound is the only one on its channel, and kira gives the sounds of a channel its volume
+/// instead of mixing the two, so the falloff is multiplied with the volume of the sound itself.
+///
+/// # Returns
+/// The volume the sound is heard at.
+fn place_sound(
+	channel: &DynamicAudioChannel,
+	volume: f64,
+	position: Vec2,
+	listener: Vec2,
+	falloff: &SoundFalloff,
+	viewport: &Viewport,
+) -> f64 {
+	let offset = position - listener;
+	let volume = volume * falloff.volume(offset.length());
+
+	channel.set_volume(volume);
+	channel.set_panning(falloff.panning(offset.x, viewport));
+
+	volume
+}
+
+pub struct AudioPlayerPlugin;
+
+impl Plugin for AudioPlayerPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_event::<PlaySound>()
+			.init_resource::<Voices>()
+			.init_resource::<AudioListener>()
+			.init_resource::<SoundFalloff>()
+			.add_startup_system(create_world_channels)
+			.add_system_to_stage(CoreStage::PostUpdate, update_listener.label("update_listener"))
+			.add_system_to_stage(CoreStage::PostUpdate, play_sounds.after("update_listener"));
+	}
+}
+
+fn create_world_channels(mut channels: ResMut<DynamicAudioChannels>) {
+	for index in 0..WORLD_CHANNELS {
+		channels.create_channel(&world_channel_key(index));
+	}
+}
+
+fn update_listener(
+	player_query: Query<&Transform, With<Player>>,
+	camera_query: Query<&Transform, With<MainCamera>>,
+	mut listener: ResMut<AudioListener>,
+) {
+	listener.0 = player_query
+		.get_single()
+		.or_else(|_| camera_query.get_single())
+		.ok()
+		.map(|transform| transform.translation.truncate());
+}
+
+/// Plays every sound asked for this frame, the most important ones first in case there aren't enough voices.
+/// The world sounds that are already playing follow the listener as it moves.
+fn play_sounds(
+	mut events: EventReader<PlaySound>,
+	mut voices: ResMut<Voices>,
+	mut rng: ResMut<GameRng>,
+	audio: Res<Audio>,
+	channels: Res<DynamicAudioChannels>,
+	settings: Res<Settings>,
+	falloff: Res<SoundFalloff>,
+	listener: Res<AudioListener>,
+	viewport: Res<Viewport>,
+	// Grouped to stay within the system parameter limit
+	(shotgun, enemy_shot, footsteps, screams, snorting, crafting, pickup, heartbeat, music): (
+		Res<ShotgunSound>,
+		Res<EnemyShotSound>,
+		Res<FootstepSounds>,
+		Res<Screams>,
+		Res<SnortingSounds>,
+		Res<CraftingSound>,
+		Res<PickupSound>,
+		Res<HeartbeatSound>,
+		Res<Music>,
+	),
+) {
+	voices.0.retain(|voice| {
+		let state = match voice.world {
+			Some((channel, _)) => world_channel(&channels, channel).state(&voice.instance),
+			None => audio.state(&voice.instance),
+		};
+
+		!matches!(state, PlaybackState::Stopped | PlaybackState::Stopping { .. })
+	});
+
+	if let Some(listener) = listener.0 {
+		for voice in voices.0.iter() {
+			if let Some((channel, position)) = voice.world {
+				let channel = world_channel(&channels, channel);
+				place_sound(channel, voice.volume, position, listener, &falloff, &viewport);
+			}
+		}
+	}
+
+	let mut requests: Vec<PlaySound> = events.iter().copied().collect();
+	requests.sort_by(|a, b| b.priority.cmp(&a.priority));
+
+	for request in requests {
+		if voices.0.len() >= request.priority.max_voices()
+			|| voices.playing(request.sound) >= request.sound.max_voices()
+		{
+			continue;
+		}
+
+		// Sounds from somewhere in the world need a channel to themselves, and are dropped if they're all taken
+		let world = match (request.position, listener.0) {
+			(Some(position), Some(listener)) => match voices.free_world_channel() {
+				Some(channel) => Some((channel, position, listener)),
+				None => continue,
+			},
+			_ => None,
+		};
+
+		let rng = rng.cosmetic();
+		let handle = match request.sound {
+			SoundId::Shotgun => Some(&shotgun.0),
+			SoundId::EnemyShot => Some(&enemy_shot.0),
+			SoundId::Footstep => footsteps.choose(rng),
+			SoundId::Scream => screams.choose(rng),
+			SoundId::Snorting => snorting.choose(rng),
+			SoundId::Crafting => Some(&crafting.0),
+			SoundId::Pickup => Some(&pickup.0),
+			SoundId::Heartbeat => Some(&heartbeat.0),
+			SoundId::Music => Some(&music.0),
+		};
+
+		let handle: Handle<AudioSource> = match handle {
+			Some(handle) => handle.clone(),
+			None => {
+				warn!("No sounds loaded for {:?}", request.sound);
+				continue;
+			}
+		};
+
+		let volume = request.category.volume(&settings) * request.sound.volume() * request.volume;
+
+		let mut command = match world {
+			Some((channel, position, listener)) => {
+				let channel = world_channel(&channels, channel);
+				let volume = place_sound(channel, volume, position, listener, &falloff, &viewport);
+
+				let mut command = channel.play(handle);
+				command.with_volume(volume);
+				command
+			}
+			None => {
+				let mut command = audio.play(handle);
+				command.with_volume(volume);
+				command
+			}
+		};
+
+		if request.sound == SoundId::Music {
+			command.loop_from(MUSIC_LOOP_START);
+		}
+
+		voices.0.push(Voice {
+			sound: request.sound,
+			instance: command.handle(),
+			volume,
+			world: world.map(|(channel, position, _)| (channel, position)),
+		});
 	}
-}
\ No newline at end of file
+}
@@ -22,6 +22,8 @@ impl Plugin for BulletPlugin {
 #[derive(Component)]
 pub struct Bullet {
 	pub speed: f32,
+	/// Where it was fired from
+	pub origin: Vec2,
 }
 
 #[derive(Bundle)]
@@ -37,7 +39,7 @@ pub struct BulletBundle {
 impl Default for BulletBundle {
 	fn default() -> Self {
 		Self {
-			bullet: Bullet { speed: 4000.0 },
+			bullet: Bullet { speed: 4000.0, origin: Vec2::ZERO },
 			collider: Collider::cuboid(BULLET_COLLIDER_WIDTH, BULLET_COLLIDER_HEIGHT),
 			sensor: Sensor,
 			sprite_bundle: SpriteBundle::default(),
@@ -46,7 +48,8 @@ impl Default for BulletBundle {
 	}
 }
 
-pub struct ShotEvent(pub Entity);
+/// The entity that got hit and where the shot came from.
+pub struct ShotEvent(pub Entity, pub Vec2);
 
 #[derive(Deref, DerefMut)]
 pub struct BulletTexture(Handle<Image>);
@@ -79,7 +82,7 @@ fn update_bullets(
 		) {
 			commands.entity(bullet_entity).despawn_recursive();
 
-			event_shot.send(ShotEvent(hit_entity));
+			event_shot.send(ShotEvent(hit_entity, bullet.origin));
 
 			continue;
 		}
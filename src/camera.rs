@@ -0,0 +1,298 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::enemy::EnemyDeathEvent;
+use crate::player::Player;
+use crate::player_input::PlayerInput;
+use crate::post_processing::MainCamera;
+use crate::settings::Settings;
+use crate::tilemap::LevelBounds;
+use crate::time::{TimeCounter, TimeLayer};
+use crate::unit::{DamageEvent, ShootEvent};
+use crate::{GameState, HEIGHT, TILE_SIZE, WIDTH};
+
+/// How much trauma goes away every second
+const TRAUMA_DECAY: f32 = 1.5;
+const MAX_SHAKE_OFFSET: f32 = 20.0;
+const SHAKE_FREQUENCY: f32 = 25.0;
+
+const SHOOT_TRAUMA: f32 = 0.25;
+/// Shots further away from the player than this don't shake the camera
+const SHOOT_TRAUMA_RANGE: f32 = 600.0;
+const PLAYER_HIT_TRAUMA: f32 = 0.6;
+const ENEMY_DEATH_TRAUMA: f32 = 0.35;
+
+const HIT_STOP_SCALE: f32 = 0.05;
+const PLAYER_HIT_STOP: f32 = 0.1;
+const ENEMY_DEATH_HIT_STOP: f32 = 0.06;
+
+/// How the main camera follows the player, can be tweaked in the inspector.
+#[derive(Reflect, Inspectable, Component)]
+#[reflect(Component)]
+pub struct CameraController {
+	/// How far the player can move from the center of the camera, on each axis, before the camera moves
+	pub deadzone: Vec2,
+	/// How quickly the camera catches up with where it should be, higher is snappier
+	pub smoothing: f32,
+	/// How far the camera moves towards the cursor, as a part of its distance from the center of the screen
+	pub look_ahead: f32,
+	pub max_look_ahead: f32,
+}
+
+impl Default for CameraController {
+	fn default() -> Self {
+		Self {
+			deadzone: Vec2::new(TILE_SIZE, TILE_SIZE * 0.75),
+			smoothing: 10.0,
+			look_ahead: 0.2,
+			max_look_ahead: TILE_SIZE * 4.0,
+		}
+	}
+}
+
+/// The size of the part of the world the camera shows, kept in sync with the window.
+/// Gameplay reads this instead of the window, so it can run without one.
+pub struct Viewport {
+	pub size: Vec2,
+}
+
+impl Default for Viewport {
+	fn default() -> Self {
+		Self {
+			size: Vec2::new(WIDTH, HEIGHT),
+		}
+	}
+}
+
+/// Where the camera would be without the shake.
+#[derive(Default)]
+struct CameraFollow {
+	/// The point the deadzone is centered on, None until the camera first catches up with the player
+	focus: Option<Vec2>,
+	position: Option<Vec2>,
+}
+
+/// Trauma-based screen shake, the shake gets stronger with the square of the trauma.
+#[derive(Default)]
+pub struct CameraShake {
+	trauma: f32,
+	/// Advanced with the time of the run instead of read from the clock, so a replay shakes the same way
+	phase: f32,
+}
+
+impl CameraShake {
+	pub fn add_trauma(&mut self, amount: f32) {
+		self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+	}
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+	fn build(&self, app: &mut App) {
+		app.register_type::<CameraController>()
+			.insert_resource(CameraFollow::default())
+			.insert_resource(CameraShake::default())
+			.init_resource::<Viewport>()
+			.add_system_to_stage(CoreStage::PreUpdate, update_viewport)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(camera_feedback.label("camera_feedback"))
+					.with_system(
+						camera_follow
+							.label("camera_follow_player")
+							.after("player_movement")
+							.after("camera_feedback"),
+					),
+			)
+			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(reset_camera));
+	}
+}
+
+/// Shakes the camera and stops the time for a moment when something violent happens.
+fn camera_feedback(
+	player_query: Query<(Entity, &Transform), With<Player>>,
+	mut shoot_events: EventReader<ShootEvent>,
+	mut damage_events: EventReader<DamageEvent>,
+	mut death_events: EventReader<EnemyDeathEvent>,
+	mut shake: ResMut<CameraShake>,
+	mut time: ResMut<TimeCounter>,
+	settings: Res<Settings>,
+) {
+	let (player, player_transform) = player_query.single();
+	let player_position = player_transform.translation.truncate();
+	let intensity = settings.screen_shake;
+
+	for shoot in shoot_events.iter() {
+		let falloff = 1.0 - (shoot.0 - player_position).length() / SHOOT_TRAUMA_RANGE;
+
+		if falloff > 0.0 {
+			shake.add_trauma(SHOOT_TRAUMA * falloff * intensity);
+		}
+	}
+
+	for damage in damage_events.iter() {
+		if damage.target == player {
+			shake.add_trauma(PLAYER_HIT_TRAUMA * intensity);
+			hit_stop(time.as_mut(), PLAYER_HIT_STOP * intensity);
+		}
+	}
+
+	for _ in death_events.iter() {
+		shake.add_trauma(ENEMY_DEATH_TRAUMA * intensity);
+		hit_stop(time.as_mut(), ENEMY_DEATH_HIT_STOP * intensity);
+	}
+}
+
+fn hit_stop(time: &mut TimeCounter, duration: f32) {
+	if duration > 0.0 {
+		time.set_scale_for(TimeLayer::HitStop, HIT_STOP_SCALE, duration);
+	}
+}
+
+/// Without a window, the viewport keeps the size it was given.
+fn update_viewport(windows: Res<Windows>, mut viewport: ResMut<Viewport>) {
+	if let Some(window) = windows.get_primary() {
+		let size = Vec2::new(window.width(), window.height());
+
+		if viewport.size != size {
+			viewport.size = size;
+		}
+	}
+}
+
+/// # Returns
+/// True if the point is on the screen, at least `margin` away from its edges.
+pub fn is_on_screen(point: Vec2, viewport: &Viewport, camera_transform: &Transform, margin: f32) -> bool {
+	let offset = (point - camera_transform.translation.truncate()).abs();
+	let half_size = viewport.size / 2.0 - margin;
+
+	offset.x < half_size.x && offset.y < half_size.y
+}
+
+/// Converts a position on the window (like the one from `Window::cursor_position`) into a point in the world,
+/// as seen by the camera with the given transform.
+///
+/// The camera's `Transform` is used instead of its `GlobalTransform`, so that systems running right after
+/// the camera has moved don't lag a frame behind. The main camera is never parented, so both are the same.
+pub fn viewport_to_world(camera: &Camera, camera_transform: &Transform, viewport_position: Vec2) -> Option<Vec2> {
+	let viewport_size = camera.logical_viewport_size()?;
+
+	let ndc = viewport_position / viewport_size * 2.0 - Vec2::ONE;
+	let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+
+	Some(ndc_to_world.project_point3(ndc.extend(-1.0)).truncate())
+}
+
+/// `viewport_to_world` for a camera that isn't rendered, like the one of the headless simulation.
+/// It has no projection, so the world is assumed to be shown at its original scale, like the main camera does.
+fn viewport_to_world_headless(viewport: &Viewport, camera_transform: &Transform, viewport_position: Vec2) -> Vec2 {
+	let offset = viewport_position - viewport.size / 2.0;
+
+	camera_transform.compute_matrix().transform_point3(offset.extend(0.0)).truncate()
+}
+
+/// Where the cursor points to in the world, if it's in the window.
+pub fn cursor_world_position(
+	input: &PlayerInput,
+	camera: Option<&Camera>,
+	viewport: &Viewport,
+	camera_transform: &Transform,
+) -> Option<Vec2> {
+	let cursor_position = input.cursor()?;
+
+	match camera {
+		Some(camera) => viewport_to_world(camera, camera_transform, cursor_position),
+		None => Some(viewport_to_world_headless(viewport, camera_transform, cursor_position)),
+	}
+}
+
+/// Moves the center of the camera so it doesn't show anything outside of the bounds.
+/// If the level is smaller than the screen, it gets centered instead.
+fn clamp_to_bounds(center: Vec2, viewport: &Viewport, bounds: &LevelBounds) -> Vec2 {
+	let half_size = viewport.size / 2.0;
+	let min = bounds.min + half_size;
+	let max = bounds.max - half_size;
+	let middle = (bounds.min + bounds.max) / 2.0;
+
+	Vec2::new(
+		if min.x <= max.x { center.x.clamp(min.x, max.x) } else { middle.x },
+		if min.y <= max.y { center.y.clamp(min.y, max.y) } else { middle.y },
+	)
+}
+
+/// Smoothly follows the player once they leave the deadzone, looking ahead towards the cursor, and adds the shake on top.
+/// Uses real time, so the camera keeps moving during hit-stop and slow motion.
+fn camera_follow(
+	player_query: Query<&Transform, With<Player>>,
+	mut camera_query: Query<(&mut Transform, &CameraController), (Without<Player>, With<MainCamera>)>,
+	mut follow: ResMut<CameraFollow>,
+	mut shake: ResMut<CameraShake>,
+	bounds: Option<Res<LevelBounds>>,
+	viewport: Res<Viewport>,
+	input: Res<PlayerInput>,
+	time: Res<TimeCounter>,
+) {
+	let (mut camera_transform, controller) = camera_query.single_mut();
+	let player_position = player_query.single().translation.truncate();
+
+	let focus = follow.focus.get_or_insert(player_position);
+	let offset = player_position - *focus;
+	*focus += (offset.abs() - controller.deadzone).max(Vec2::ZERO) * offset.signum();
+
+	// Measured from the center of the window, so the camera moving doesn't move the target
+	let look_ahead = input
+		.cursor()
+		.map(|cursor_position| cursor_position - viewport.size / 2.0)
+		.unwrap_or_default()
+		* controller.look_ahead;
+
+	let mut target = *focus + look_ahead.clamp_length_max(controller.max_look_ahead);
+
+	if let Some(bounds) = &bounds {
+		target = clamp_to_bounds(target, &viewport, bounds);
+	}
+
+	let delta = time.real_delta_seconds();
+
+	let position = match follow.position {
+		Some(position) => position.lerp(target, 1.0 - (-controller.smoothing * delta).exp()),
+		None => target,
+	};
+
+	follow.position = Some(position);
+
+	shake.trauma = (shake.trauma - TRAUMA_DECAY * delta).max(0.0);
+
+	shake.phase += delta;
+
+	let strength = shake.trauma * shake.trauma;
+	let t = shake.phase * SHAKE_FREQUENCY;
+
+	// Sines with unrelated frequencies look random enough without needing a noise function
+	let offset = Vec2::new(
+		(t * 1.13).sin() + (t * 2.71).sin() * 0.5,
+		(t * 1.37).cos() + (t * 3.07).sin() * 0.5,
+	) / 1.5 * MAX_SHAKE_OFFSET * strength;
+
+	let mut shaken = position + offset;
+
+	if let Some(bounds) = &bounds {
+		shaken = clamp_to_bounds(shaken, &viewport, bounds);
+	}
+
+	camera_transform.translation.x = shaken.x;
+	camera_transform.translation.y = shaken.y;
+}
+
+fn reset_camera(
+	mut follow: ResMut<CameraFollow>,
+	mut shake: ResMut<CameraShake>,
+	mut time: ResMut<TimeCounter>,
+) {
+	follow.focus = None;
+	follow.position = None;
+	shake.trauma = 0.0;
+	shake.phase = 0.0;
+	time.clear_scale(TimeLayer::HitStop);
+}
@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::player::Player;
+use crate::stats::{CheckpointStats, Stats};
+use crate::tilemap::{Tile, TilePosition};
+use crate::unit::{Health, Inventory};
+use crate::{GameState, TILE_SIZE};
+
+pub struct CheckpointPlugin;
+
+impl Plugin for CheckpointPlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(Checkpoints::default())
+			.add_event::<CheckpointReachedEvent>()
+			.add_system_set(
+				SystemSet::on_enter(GameState::Game).with_system(
+					begin_attempt
+						.label("begin_attempt")
+						.after("load_level")
+						.after("reset_stats"),
+				),
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game).with_system(
+					reach_checkpoint
+						.label("reach_checkpoint")
+						.after("restore_save"),
+				),
+			);
+	}
+}
+
+#[derive(Component, Default)]
+pub struct Checkpoint;
+
+/// Sent the first time the player reaches a checkpoint in a run, with the tile of the checkpoint.
+pub struct CheckpointReachedEvent(pub IVec2);
+
+#[derive(Bundle, Default)]
+pub struct CheckpointBundle {
+	#[bundle]
+	sprite_bundle: SpriteBundle,
+	checkpoint: Checkpoint,
+}
+
+impl Tile for CheckpointBundle {
+	fn spawn(position: Vec2, texture: Handle<Image>, flip_x: bool, flip_y: bool) -> Self {
+		Self {
+			sprite_bundle: SpriteBundle {
+				transform: Transform::from_translation(position.extend(15.0)),
+				sprite: Sprite {
+					flip_x,
+					flip_y,
+					color: UNREACHED_COLOR,
+					..Default::default()
+				},
+				texture,
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+}
+
+const UNREACHED_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.5);
+const REACHED_COLOR: Color = Color::WHITE;
+
+/// Everything needed to put the player back at a checkpoint.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CheckpointSnapshot {
+	pub position: Vec2,
+	pub health: Health,
+	pub inventory: Inventory,
+	pub killed_enemies: HashSet<IVec2>,
+	pub collected_pickups: HashSet<IVec2>,
+	reached_checkpoints: HashSet<IVec2>,
+	pub stats: CheckpointStats,
+}
+
+/// Progress of the current run.
+///
+/// Enemies and pickups are identified by the tile they were placed on in the level file,
+/// since their entities don't survive reloading the level.
+#[derive(Default)]
+pub struct Checkpoints {
+	last: Option<CheckpointSnapshot>,
+	killed_enemies: HashSet<IVec2>,
+	collected_pickups: HashSet<IVec2>,
+	reached_checkpoints: HashSet<IVec2>,
+	respawning: bool,
+}
+
+impl Checkpoints {
+	/// Makes the next level load restore the last checkpoint instead of starting a new run.
+	pub fn request_respawn(&mut self) {
+		self.respawning = true;
+	}
+
+	/// # Returns
+	/// The snapshot the level is being restored from, if the player is respawning at a checkpoint.
+	pub fn respawn_point(&self) -> Option<&CheckpointSnapshot> {
+		if self.respawning {
+			self.last.as_ref()
+		} else {
+			None
+		}
+	}
+
+	pub fn last(&self) -> Option<&CheckpointSnapshot> {
+		self.last.as_ref()
+	}
+
+	/// Puts back the last checkpoint of a continued run, so dying respawns there.
+	pub fn restore(&mut self, last: Option<CheckpointSnapshot>) {
+		if let Some(snapshot) = &last {
+			self.reached_checkpoints = snapshot.reached_checkpoints.clone();
+		}
+
+		self.last = last;
+	}
+
+	/// False if no checkpoint was reached, dying then restarts the run from scratch.
+	pub fn is_respawning(&self) -> bool {
+		self.respawning && self.last.is_some()
+	}
+
+	pub fn enemy_killed(&mut self, tile: IVec2) {
+		self.killed_enemies.insert(tile);
+	}
+
+	pub fn pickup_collected(&mut self, tile: IVec2) {
+		self.collected_pickups.insert(tile);
+	}
+
+	pub fn is_reached(&self, tile: IVec2) -> bool {
+		self.reached_checkpoints.contains(&tile)
+	}
+}
+
+fn begin_attempt(mut checkpoints: ResMut<Checkpoints>) {
+	let checkpoints = checkpoints.as_mut();
+
+	match checkpoints.respawn_point().cloned() {
+		Some(snapshot) => {
+			checkpoints.killed_enemies = snapshot.killed_enemies;
+			checkpoints.collected_pickups = snapshot.collected_pickups;
+			checkpoints.reached_checkpoints = snapshot.reached_checkpoints;
+		}
+		None => {
+			// A fresh run, forget everything from the previous one
+			*checkpoints = Checkpoints::default();
+		}
+	}
+
+	checkpoints.respawning = false;
+}
+
+fn reach_checkpoint(
+	player_query: Query<(&Transform, &Health, &Inventory), With<Player>>,
+	mut checkpoint_query: Query<(&Transform, &TilePosition, &mut Sprite), (With<Checkpoint>, Without<Player>)>,
+	mut checkpoints: ResMut<Checkpoints>,
+	stats: Res<Stats>,
+	mut reached_events: EventWriter<CheckpointReachedEvent>,
+) {
+	let (player_transform, health, inventory) = player_query.single();
+	let player_position = player_transform.translation.truncate();
+
+	for (checkpoint_transform, tile, mut sprite) in checkpoint_query.iter_mut() {
+		if checkpoints.is_reached(tile.0) {
+			if sprite.color != REACHED_COLOR {
+				sprite.color = REACHED_COLOR;
+			}
+
+			continue;
+		}
+
+		let checkpoint_position = checkpoint_transform.translation.truncate();
+
+		if (player_position - checkpoint_position).length() > TILE_SIZE {
+			continue;
+		}
+
+		checkpoints.reached_checkpoints.insert(tile.0);
+
+		checkpoints.last = Some(CheckpointSnapshot {
+			position: checkpoint_position,
+			health: health.clone(),
+			inventory: inventory.clone(),
+			killed_enemies: checkpoints.killed_enemies.clone(),
+			collected_pickups: checkpoints.collected_pickups.clone(),
+			reached_checkpoints: checkpoints.reached_checkpoints.clone(),
+			stats: CheckpointStats::of(&stats),
+		});
+
+		sprite.color = REACHED_COLOR;
+
+		reached_events.send(CheckpointReachedEvent(tile.0));
+	}
+}
@@ -0,0 +1,416 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::audio_player::{PlaySound, SoundId};
+use crate::fonts::RobotoFont;
+use crate::item::ItemRegistry;
+use crate::player::Player;
+use crate::player_input::PlayerInput;
+use crate::stats::Stats;
+use crate::time::TimeCounter;
+use crate::unit::{DamageEvent, Inventory};
+use crate::{GameState, TILE_SIZE};
+
+/// How close the player has to stand to a station to use it
+const STATION_RANGE: f32 = TILE_SIZE * 1.5;
+
+const DIGIT_KEYS: [char; 9] = ['1', '2', '3', '4', '5', '6', '7', '8', '9'];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemAmount {
+	pub item: String,
+	pub quantity: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecipeDefinition {
+	pub id: String,
+	pub name: String,
+	pub inputs: Vec<ItemAmount>,
+	pub outputs: Vec<ItemAmount>,
+	/// In seconds of game time
+	pub time: f32,
+	/// The station the player has to stand next to, if any
+	#[serde(default)]
+	pub station: Option<String>,
+}
+
+impl RecipeDefinition {
+	fn can_craft(&self, inventory: &Inventory, items: &ItemRegistry, nearby_stations: &[&str]) -> bool {
+		let has_station = self
+			.station
+			.as_ref()
+			.map_or(true, |station| nearby_stations.contains(&station.as_str()));
+
+		let has_inputs = self
+			.inputs
+			.iter()
+			.all(|input| inventory.quantity(&input.item) >= input.quantity);
+
+		let outputs_fit = self.outputs.iter().all(|output| {
+			items.get(&output.item).map_or(false, |item| {
+				inventory.quantity(&output.item) + output.quantity <= item.max_stack
+			})
+		});
+
+		has_station && has_inputs && outputs_fit
+	}
+
+	fn describe(&self, items: &ItemRegistry) -> String {
+		let inputs = self
+			.inputs
+			.iter()
+			.map(|input| {
+				let name = items.get(&input.item).map_or(input.item.as_str(), |item| item.name.as_str());
+
+				format!("{} {}", input.quantity, name)
+			})
+			.collect::<Vec<_>>()
+			.join(", ");
+
+		format!("{} ({}) - {:.1}s", self.name, inputs, self.time)
+	}
+}
+
+/// All the recipes defined in `assets/items/recipes.ron`.
+pub struct RecipeRegistry {
+	recipes: Vec<RecipeDefinition>,
+}
+
+impl RecipeRegistry {
+	fn load() -> Self {
+		// Included in the binary like the level, so it works on the web as well
+		let recipes = ron::from_str(include_str!("../assets/items/recipes.ron")).expect("Invalid recipes!");
+
+		Self { recipes }
+	}
+
+	pub fn get(&self, id: &str) -> Option<&RecipeDefinition> {
+		self.recipes.iter().find(|recipe| recipe.id == id)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &RecipeDefinition> {
+		self.recipes.iter()
+	}
+}
+
+/// A tile the player can craft the recipes that need it next to.
+#[derive(Component)]
+pub struct CraftingStation(pub String);
+
+/// Put on the player while a recipe is being crafted.
+#[derive(Component)]
+struct Crafting {
+	recipe: String,
+	timer: Timer,
+}
+
+#[derive(Default)]
+struct CraftingMenu {
+	open: bool,
+	/// The ids of the recipes listed in the menu, in the order of their hotkeys
+	listed: Vec<String>,
+}
+
+#[derive(Component)]
+struct CraftingUi;
+
+#[derive(Component)]
+struct RecipeList;
+
+#[derive(Component)]
+struct CraftingBarContainer;
+
+#[derive(Component)]
+struct CraftingBar;
+
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(RecipeRegistry::load())
+			.insert_resource(CraftingMenu::default())
+			.add_system_set(
+				SystemSet::on_enter(GameState::Game)
+					.with_system(load_ui)
+					.with_system(close_crafting_menu),
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(toggle_crafting_menu.label("toggle_crafting_menu"))
+					.with_system(
+						update_recipe_list
+							.label("update_recipe_list")
+							.after("toggle_crafting_menu"),
+					)
+					.with_system(start_crafting.label("start_crafting").after("update_recipe_list"))
+					.with_system(update_crafting.label("update_crafting").after("start_crafting"))
+					.with_system(update_crafting_bar.after("update_crafting")),
+			)
+			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(drop_ui));
+	}
+}
+
+fn nearby_stations<'a>(
+	player_position: Vec2,
+	station_query: &'a Query<(&Transform, &CraftingStation)>,
+) -> Vec<&'a str> {
+	station_query
+		.iter()
+		.filter(|(transform, _)| {
+			(transform.translation.truncate() - player_position).length() <= STATION_RANGE
+		})
+		.map(|(_, station)| station.0.as_str())
+		.collect()
+}
+
+fn close_crafting_menu(mut menu: ResMut<CraftingMenu>) {
+	*menu = CraftingMenu::default();
+}
+
+fn toggle_crafting_menu(input: Res<PlayerInput>, mut menu: ResMut<CraftingMenu>) {
+	// Press T to open the crafting menu
+	if input.just_pressed('T') {
+		menu.open = !menu.open;
+	}
+}
+
+/// Lists the recipes that can be crafted right now, numbered with their hotkeys.
+fn update_recipe_list(
+	mut commands: Commands,
+	player_query: Query<(&Transform, &Inventory), With<Player>>,
+	station_query: Query<(&Transform, &CraftingStation)>,
+	mut list_query: Query<(Entity, &mut Style), With<RecipeList>>,
+	recipes: Res<RecipeRegistry>,
+	items: Res<ItemRegistry>,
+	mut menu: ResMut<CraftingMenu>,
+	font: Res<RobotoFont>,
+	mut was_open: Local<bool>,
+) {
+	let (list, mut list_style) = list_query.single_mut();
+
+	let just_opened = menu.open && !*was_open;
+	*was_open = menu.open;
+
+	if !menu.open {
+		list_style.display = Display::None;
+		return;
+	}
+
+	list_style.display = Display::Flex;
+
+	let (player_transform, inventory) = player_query.single();
+	let stations = nearby_stations(player_transform.translation.truncate(), &station_query);
+
+	let craftable: Vec<String> = recipes
+		.iter()
+		.filter(|recipe| recipe.can_craft(inventory, &items, &stations))
+		.take(DIGIT_KEYS.len())
+		.map(|recipe| recipe.id.clone())
+		.collect();
+
+	// Only rebuild the list when it actually changes
+	if !just_opened && craftable == menu.listed {
+		return;
+	}
+
+	menu.listed = craftable;
+
+	let text_style = TextStyle {
+		font: font.0.clone(),
+		font_size: 24.0,
+		color: Color::WHITE,
+	};
+
+	commands.entity(list).despawn_descendants();
+	commands.entity(list).with_children(|parent| {
+		parent.spawn_bundle(TextBundle::from_section("Crafting [T]", text_style.clone()));
+
+		if menu.listed.is_empty() {
+			parent.spawn_bundle(TextBundle::from_section("Nothing to craft", text_style.clone()));
+		}
+
+		for (index, id) in menu.listed.iter().enumerate() {
+			if let Some(recipe) = recipes.get(id) {
+				parent.spawn_bundle(TextBundle::from_section(
+					format!("{}. {}", index + 1, recipe.describe(&items)),
+					text_style.clone(),
+				));
+			}
+		}
+	});
+}
+
+fn start_crafting(
+	mut commands: Commands,
+	player_query: Query<Entity, (With<Player>, Without<Crafting>)>,
+	input: Res<PlayerInput>,
+	recipes: Res<RecipeRegistry>,
+	mut menu: ResMut<CraftingMenu>,
+) {
+	if !menu.open {
+		return;
+	}
+
+	let player = match player_query.get_single() {
+		Ok(player) => player,
+		// Already crafting something
+		Err(_) => return,
+	};
+
+	let recipe = DIGIT_KEYS
+		.iter()
+		.zip(menu.listed.iter())
+		.find(|(key, _)| input.just_pressed(**key))
+		.and_then(|(_, id)| recipes.get(id));
+
+	if let Some(recipe) = recipe {
+		commands.entity(player).insert(Crafting {
+			recipe: recipe.id.clone(),
+			timer: Timer::from_seconds(recipe.time, false),
+		});
+
+		menu.open = false;
+	}
+}
+
+/// Advances the crafting and finishes it, unless the player got hurt or can't craft the recipe anymore.
+fn update_crafting(
+	mut commands: Commands,
+	mut player_query: Query<(Entity, &Transform, &mut Inventory, &mut Crafting), With<Player>>,
+	station_query: Query<(&Transform, &CraftingStation)>,
+	recipes: Res<RecipeRegistry>,
+	items: Res<ItemRegistry>,
+	time: Res<TimeCounter>,
+	mut sounds: EventWriter<PlaySound>,
+	mut stats: ResMut<Stats>,
+	mut damage_events: EventReader<DamageEvent>,
+) {
+	// Read every frame, so hits from before the crafting started don't interrupt it
+	let hit_targets: Vec<Entity> = damage_events.iter().map(|damage| damage.target).collect();
+
+	let (player, player_transform, mut inventory, mut crafting) = match player_query.get_single_mut() {
+		Ok(player) => player,
+		Err(_) => return,
+	};
+
+	// Only getting hit counts, the health the drugs drain doesn't
+	let interrupted = hit_targets.contains(&player);
+
+	let stations = nearby_stations(player_transform.translation.truncate(), &station_query);
+
+	let recipe = match recipes.get(&crafting.recipe) {
+		Some(recipe) if !interrupted && recipe.can_craft(&inventory, &items, &stations) => recipe,
+		_ => {
+			commands.entity(player).remove::<Crafting>();
+			return;
+		}
+	};
+
+	crafting.timer.tick(time.delta());
+
+	if !crafting.timer.finished() {
+		return;
+	}
+
+	for input in &recipe.inputs {
+		inventory.remove(&input.item, input.quantity);
+	}
+
+	for output in &recipe.outputs {
+		if let Some(item) = items.get(&output.item) {
+			inventory.add(&output.item, output.quantity, item.max_stack);
+		}
+	}
+
+	sounds.send(PlaySound::new(SoundId::Crafting));
+
+	*stats.recipes_crafted.entry(recipe.id.clone()).or_default() += 1;
+
+	commands.entity(player).remove::<Crafting>();
+}
+
+fn update_crafting_bar(
+	player_query: Query<Option<&Crafting>, With<Player>>,
+	mut container_query: Query<&mut Style, (With<CraftingBarContainer>, Without<CraftingBar>)>,
+	mut bar_query: Query<&mut Style, (With<CraftingBar>, Without<CraftingBarContainer>)>,
+) {
+	let mut container_style = container_query.single_mut();
+	let mut bar_style = bar_query.single_mut();
+
+	match player_query.single() {
+		Some(crafting) => {
+			container_style.display = Display::Flex;
+			bar_style.size.width = Val::Percent(100.0 * crafting.timer.percent());
+		}
+		None => {
+			container_style.display = Display::None;
+		}
+	}
+}
+
+fn load_ui(mut commands: Commands) {
+	commands
+		.spawn_bundle(NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect::new(Val::Percent(30.0), Val::Auto, Val::Auto, Val::Px(100.0)),
+				size: Size::new(Val::Percent(40.0), Val::Auto),
+				flex_direction: FlexDirection::ColumnReverse,
+				align_items: AlignItems::Center,
+				..Default::default()
+			},
+			color: Color::NONE.into(),
+			..Default::default()
+		})
+		.insert(CraftingUi)
+		.insert(Name::new("CraftingUi"))
+		.with_children(|parent| {
+			parent
+				.spawn_bundle(NodeBundle {
+					style: Style {
+						display: Display::None,
+						flex_direction: FlexDirection::ColumnReverse,
+						padding: UiRect::all(Val::Px(10.0)),
+						margin: UiRect::new(Val::Px(0.0), Val::Px(0.0), Val::Px(0.0), Val::Px(10.0)),
+						..Default::default()
+					},
+					color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+					..Default::default()
+				})
+				.insert(RecipeList)
+				.insert(Name::new("RecipeList"));
+
+			parent
+				.spawn_bundle(NodeBundle {
+					style: Style {
+						display: Display::None,
+						size: Size::new(Val::Percent(100.0), Val::Px(30.0)),
+						padding: UiRect::all(Val::Px(7.0)),
+						..Default::default()
+					},
+					color: Color::BLACK.into(),
+					..Default::default()
+				})
+				.insert(CraftingBarContainer)
+				.insert(Name::new("CraftingBarContainer"))
+				.with_children(|parent| {
+					parent
+						.spawn_bundle(NodeBundle {
+							style: Style {
+								size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+								..Default::default()
+							},
+							color: Color::PINK.into(),
+							..Default::default()
+						})
+						.insert(CraftingBar)
+						.insert(Name::new("CraftingBar"));
+				});
+		});
+}
+
+fn drop_ui(mut commands: Commands, ui: Query<Entity, With<CraftingUi>>) {
+	let ui = ui.single();
+	commands.entity(ui).despawn_recursive();
+}
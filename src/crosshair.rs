@@ -1,6 +1,20 @@
 use bevy::prelude::*;
 
-use crate::{GameState, HEIGHT, WIDTH, post_processing::MainCamera};
+use crate::{
+	GameState,
+	camera::{cursor_world_position, Viewport},
+	player::Player,
+	player_input::PlayerInput,
+	post_processing::MainCamera,
+	time::TimeCounter,
+	unit::DamageEvent,
+};
+
+/// How far from the crosshair the hit indicators are
+const HIT_INDICATOR_RADIUS: f32 = 40.0;
+const HIT_INDICATOR_SIZE: Vec2 = Vec2::new(24.0, 6.0);
+const HIT_INDICATOR_DURATION: f32 = 0.8;
+const HIT_INDICATOR_COLOR: Color = Color::rgb(0.9, 0.1, 0.1);
 
 pub struct CrosshairPlugin;
 
@@ -8,14 +22,31 @@ impl Plugin for CrosshairPlugin {
 	fn build(&self, app: &mut App) {
 		app.add_startup_system(load_crosshair_sprite)
 			.add_system_set(SystemSet::on_enter(GameState::Game).with_system(crosshair_setup))
-			.add_system_set(SystemSet::on_update(GameState::Game).with_system(crosshair_update.after("camera_follow_player")))
-			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(crosshair_drop));
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(crosshair_update.label("crosshair_update").after("camera_follow_player"))
+					.with_system(spawn_hit_indicators)
+					.with_system(update_hit_indicators.after("crosshair_update")),
+			)
+			.add_system_set(
+				SystemSet::on_exit(GameState::Game)
+					.with_system(crosshair_drop)
+					.with_system(drop_hit_indicators),
+			);
 	}
 }
 
 #[derive(Component)]
 pub struct Crosshair;
 
+/// Points from the crosshair towards whoever hit the player, then fades out.
+#[derive(Component)]
+struct HitIndicator {
+	/// From the player towards the attacker
+	direction: Vec2,
+	timer: Timer,
+}
+
 #[derive(Deref, DerefMut)]
 struct CrosshairSprite(Handle<Image>);
 
@@ -43,19 +74,15 @@ fn crosshair_setup(
 
 fn crosshair_update(
 	mut crosshair_query: Query<&mut Transform, With<Crosshair>>,
-	camera_query: Query<&Transform, (With<MainCamera>, Without<Crosshair>)>,
-	windows: Res<Windows>,
+	camera_query: Query<(Option<&Camera>, &Transform), (With<MainCamera>, Without<Crosshair>)>,
+	input: Res<PlayerInput>,
+	viewport: Res<Viewport>,
 ) {
 	let mut crosshair_transform = crosshair_query.single_mut();
-	let camera_transform = camera_query.single();
-	let window = windows.get_primary().unwrap();
+	let (camera, camera_transform) = camera_query.single();
 
-	if let Some(cursor_position) = window.cursor_position() {
-		crosshair_transform.translation = camera_transform.translation
-			- Vec3::new(WIDTH, HEIGHT, 0.0) / 2.0
-			+ cursor_position.extend(0.0);
-
-			crosshair_transform.translation.z = 100.0;
+	if let Some(position) = cursor_world_position(input.as_ref(), camera, viewport.as_ref(), camera_transform) {
+		crosshair_transform.translation = position.extend(100.0);
 	}
 }
 
@@ -71,3 +98,72 @@ fn crosshair_drop(
 
 	commands.entity(crosshair).despawn_recursive();
 }
+
+fn spawn_hit_indicators(
+	mut commands: Commands,
+	player_query: Query<(Entity, &Transform), With<Player>>,
+	mut damage_events: EventReader<DamageEvent>,
+) {
+	let (player, player_transform) = player_query.single();
+
+	for damage in damage_events.iter() {
+		if damage.target != player {
+			continue;
+		}
+
+		let direction = (damage.source - player_transform.translation.truncate()).normalize_or_zero();
+
+		// Shot from point blank, there's nothing to point at
+		if direction == Vec2::ZERO {
+			continue;
+		}
+
+		commands
+			.spawn_bundle(SpriteBundle {
+				sprite: Sprite {
+					color: HIT_INDICATOR_COLOR,
+					custom_size: Some(HIT_INDICATOR_SIZE),
+					..Default::default()
+				},
+				visibility: Visibility { is_visible: false },
+				..Default::default()
+			})
+			.insert(HitIndicator {
+				direction,
+				timer: Timer::from_seconds(HIT_INDICATOR_DURATION, false),
+			})
+			.insert(Name::new("HitIndicator"));
+	}
+}
+
+/// Keeps the indicators around the crosshair and fades them out, in real time so they don't hang during the hit-stop.
+fn update_hit_indicators(
+	mut commands: Commands,
+	mut indicator_query: Query<(Entity, &mut Transform, &mut Sprite, &mut Visibility, &mut HitIndicator)>,
+	crosshair_query: Query<&Transform, (With<Crosshair>, Without<HitIndicator>)>,
+	time: Res<TimeCounter>,
+) {
+	let crosshair_position = crosshair_query.single().translation;
+
+	for (entity, mut transform, mut sprite, mut visibility, mut indicator) in indicator_query.iter_mut() {
+		indicator.timer.tick(time.real_delta());
+
+		if indicator.timer.finished() {
+			commands.entity(entity).despawn_recursive();
+			continue;
+		}
+
+		// Lies across the direction, like a piece of a ring around the crosshair
+		transform.translation = crosshair_position + (indicator.direction * HIT_INDICATOR_RADIUS).extend(0.0);
+		transform.rotation = Quat::from_rotation_z(indicator.direction.y.atan2(indicator.direction.x) + std::f32::consts::FRAC_PI_2);
+
+		sprite.color.set_a(1.0 - indicator.timer.percent());
+		visibility.is_visible = true;
+	}
+}
+
+fn drop_hit_indicators(mut commands: Commands, indicator_query: Query<Entity, With<HitIndicator>>) {
+	for entity in indicator_query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
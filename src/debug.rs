@@ -1,13 +1,18 @@
 use bevy::prelude::*;
-use bevy_inspector_egui::WorldInspectorPlugin;
+use bevy_inspector_egui::{InspectorPlugin, WorldInspectorPlugin};
 use bevy_prototype_debug_lines::DebugLinesPlugin;
 
+use crate::audio_player::SoundFalloff;
+use crate::shader_params::ShaderTuning;
+
 pub struct DebugPlugin;
 
 impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
         if cfg!(debug_assertions) {
             app.add_plugin(WorldInspectorPlugin::new())
+                .add_plugin(InspectorPlugin::<ShaderTuning>::new())
+                .add_plugin(InspectorPlugin::<SoundFalloff>::new())
                 .add_plugin(DebugLinesPlugin::default());
         }
     }
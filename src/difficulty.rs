@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::Checkpoints;
+use crate::player::HealthTuning;
+use crate::save::PendingLoad;
+use crate::settings::Settings;
+use crate::GameState;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+	Easy,
+	#[default]
+	Normal,
+	Hard,
+}
+
+impl Difficulty {
+	pub fn next(self) -> Self {
+		match self {
+			Difficulty::Easy => Difficulty::Normal,
+			Difficulty::Normal => Difficulty::Hard,
+			Difficulty::Hard => Difficulty::Easy,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Difficulty::Easy => "Easy",
+			Difficulty::Normal => "Normal",
+			Difficulty::Hard => "Hard",
+		}
+	}
+
+	fn scaling(self) -> DifficultyScaling {
+		match self {
+			Difficulty::Easy => DifficultyScaling {
+				perception: 0.75,
+				shock_duration: 1.5,
+				enemy_cooldown: 1.4,
+				enemy_damage: 0.6,
+				health: HealthTuning {
+					regeneration_delay: Some(3.0),
+					regeneration_per_second: 6.0,
+					regeneration_limit: 0.75,
+				},
+			},
+			Difficulty::Normal => DifficultyScaling {
+				perception: 1.0,
+				shock_duration: 1.0,
+				enemy_cooldown: 1.0,
+				enemy_damage: 1.0,
+				health: HealthTuning {
+					regeneration_delay: Some(5.0),
+					regeneration_per_second: 4.0,
+					regeneration_limit: 0.5,
+				},
+			},
+			Difficulty::Hard => DifficultyScaling {
+				perception: 1.25,
+				shock_duration: 0.6,
+				enemy_cooldown: 0.7,
+				enemy_damage: 1.4,
+				health: HealthTuning {
+					regeneration_delay: None,
+					regeneration_per_second: 0.0,
+					regeneration_limit: 0.0,
+				},
+			},
+		}
+	}
+}
+
+/// The difficulty of the run being played.
+/// The one in the settings only applies to new runs, a continued run keeps the difficulty it was saved on.
+#[derive(Deref, Default)]
+pub struct RunDifficulty(Difficulty);
+
+/// Everything the difficulty changes, kept in sync with the difficulty of the run.
+/// The enemy values multiply the constants in `enemy.rs`.
+pub struct DifficultyScaling {
+	/// How far enemies can see and hear
+	pub perception: f32,
+	/// How long enemies take to react after spotting the player
+	pub shock_duration: f32,
+	/// The time between enemy shots
+	pub enemy_cooldown: f32,
+	pub enemy_damage: f32,
+	pub health: HealthTuning,
+}
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(Difficulty::default().scaling())
+			.init_resource::<RunDifficulty>()
+			.add_system_set(
+				SystemSet::on_enter(GameState::Game).with_system(begin_run_difficulty.before("begin_attempt")),
+			)
+			.add_system(update_difficulty_scaling);
+	}
+}
+
+fn begin_run_difficulty(
+	settings: Res<Settings>,
+	pending_load: Res<PendingLoad>,
+	checkpoints: Res<Checkpoints>,
+	mut run_difficulty: ResMut<RunDifficulty>,
+) {
+	// Respawning is still the same run
+	if checkpoints.is_respawning() {
+		return;
+	}
+
+	run_difficulty.0 = match &pending_load.0 {
+		Some(save) => save.difficulty(),
+		None => settings.difficulty,
+	};
+}
+
+fn update_difficulty_scaling(run_difficulty: Res<RunDifficulty>, mut scaling: ResMut<DifficultyScaling>) {
+	if run_difficulty.is_changed() {
+		*scaling = run_difficulty.scaling();
+	}
+}
@@ -3,23 +3,24 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use bevy::prelude::*;
-use bevy_kira_audio::Audio;
 use bevy_rapier2d::prelude::*;
-use navmesh::NavVec3;
-use rand::random;
+use rand::Rng;
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
 
-use crate::audio::{EnemyShotSound, Screams};
-use crate::audio_player::{AudioPlayer, ENEMY_SHOT_VOLUME, ENEMY_DEATH_SCREAM_VOLUME};
+use crate::audio_player::{PlaySound, SoundId};
+use crate::camera::{is_on_screen, Viewport};
+use crate::checkpoint::Checkpoints;
+use crate::difficulty::DifficultyScaling;
 use crate::bullet::{
 	Bullet, BulletBundle, BulletTexture, ShotEvent, BULLET_COLLIDER_HEIGHT, BULLET_COLLIDER_WIDTH,
 };
 use crate::enemy_nav_mesh::EnemyNavMesh;
 use crate::player::Player;
 use crate::post_processing::MainCamera;
+use crate::rng::GameRng;
 use crate::stats::Stats;
-use crate::settings::Settings;
-use crate::tilemap::{TexturesMemo, Tile, Tilemap};
+use crate::tilemap::{TexturesMemo, Tile, TilePosition, Tilemap};
 use crate::time::TimeCounter;
 use crate::unit::{Movement, ShootEvent, Shooting};
 use crate::{GameState, TILE_SIZE};
@@ -27,19 +28,25 @@ use crate::{GameState, TILE_SIZE};
 pub const ENEMY_SIGHT: f32 = 12.0 * TILE_SIZE;
 pub const ENEMY_HEARING: f32 = 10.0 * TILE_SIZE;
 pub const SHOCK_DURATION: f32 = 0.5;
+pub const ENEMY_COOLDOWN: f32 = 1.0;
 
 pub struct EnemyPlugin;
 
+/// Sent with the position of an enemy when it dies.
+pub struct EnemyDeathEvent(pub Vec2);
+
 impl Plugin for EnemyPlugin {
 	fn build(&self, app: &mut App) {
-		app.add_startup_system(load_enemy_textures).add_system_set(
-			SystemSet::on_update(GameState::Game)
-				.with_system(update_enemy_ai.label("update_enemy_ai"))
-				.with_system(alert_on_shot_sound)
-				.with_system(update_enemy_position.after("update_enemy_ai"))
-				.with_system(update_enemy_texture)
-				.with_system(get_shot),
-		);
+		app.add_event::<EnemyDeathEvent>()
+			.add_startup_system(load_enemy_textures)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(update_enemy_ai.label("update_enemy_ai"))
+					.with_system(alert_on_shot_sound)
+					.with_system(update_enemy_position.after("update_enemy_ai"))
+					.with_system(update_enemy_texture)
+					.with_system(get_shot),
+			);
 	}
 }
 
@@ -49,6 +56,16 @@ pub struct Enemy {
 	shock_timer: Timer,
 }
 
+impl Enemy {
+	pub fn ai_state(&self) -> &EnemyAiState {
+		&self.ai_state
+	}
+
+	pub fn set_ai_state(&mut self, ai_state: EnemyAiState) {
+		self.ai_state = ai_state;
+	}
+}
+
 #[derive(Bundle)]
 pub struct EnemyBundle {
 	#[bundle]
@@ -78,7 +95,7 @@ impl Default for EnemyBundle {
 			},
 			movement: Movement { speed: 3.0 },
 			shooting: Shooting {
-				cooldown: Timer::from_seconds(1.0, false),
+				cooldown: Timer::from_seconds(ENEMY_COOLDOWN, false),
 			},
 			rapier_collider: Collider::cuboid(TILE_SIZE / 2.0, TILE_SIZE / 2.0),
 		}
@@ -129,10 +146,11 @@ pub struct EnemyTextures {
 	pub blood_splatter: Handle<Image>,
 }
 
-enum EnemyAiState {
+#[derive(Clone, Serialize, Deserialize)]
+pub enum EnemyAiState {
 	Idle,
 	Alert {
-		path: Option<Vec<NavVec3>>,
+		path: Option<Vec<Vec2>>,
 		current: usize,
 	},
 	Combat {
@@ -140,11 +158,17 @@ enum EnemyAiState {
 	},
 }
 
-fn is_on_screen(point: Vec2, window: &Window, camera: &Transform) -> bool {
-	let screen_position = point - camera.translation.truncate();
-
-	screen_position.x.abs() < (window.width() - TILE_SIZE) / 2.0
-		&& screen_position.y.abs() < (window.height() - TILE_SIZE) / 2.0
+fn find_path(nav_mesh: &EnemyNavMesh, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+	nav_mesh
+		.get_nav_mesh()
+		.expect("The nav mesh has not been baked!")
+		.find_path(
+			from.extend(0.0).to_array().into(),
+			to.extend(0.0).to_array().into(),
+			navmesh::NavQuery::Closest,
+			navmesh::NavPathMode::Accuracy,
+		)
+		.map(|path| path.iter().map(|point| Vec2::new(point.x, point.y)).collect())
 }
 
 fn update_enemy_ai(
@@ -161,16 +185,16 @@ fn update_enemy_ai(
 			Without<Tilemap>,
 		),
 	>,
-	mut shoot_event: EventWriter<ShootEvent>,
-	mut shot_event: EventWriter<ShotEvent>,
+	// Grouped to stay within the system parameter limit
+	(mut shoot_event, mut shot_event): (EventWriter<ShootEvent>, EventWriter<ShotEvent>),
 	rapier_context: Res<RapierContext>,
 	time: Res<TimeCounter>,
-	settings: Res<Settings>,
-	windows: Res<Windows>,
+	viewport: Res<Viewport>,
 	nav_mesh: Res<EnemyNavMesh>,
-	audio: Res<Audio>,
-	shot_sound: Res<EnemyShotSound>,
+	mut sounds: EventWriter<PlaySound>,
 	bullet_texture: Res<BulletTexture>,
+	difficulty: Res<DifficultyScaling>,
+	mut rng: ResMut<GameRng>,
 ) {
 	let (player, player_transform) = player.single_mut();
 	let tilemap = tilemap.single();
@@ -179,6 +203,14 @@ fn update_enemy_ai(
 	let player_position = player_transform.translation.truncate();
 
 	for (entity, mut transform, mut shooting, mut enemy) in enemies.iter_mut() {
+		// The difficulty can change between runs, so the timers follow it
+		shooting
+			.cooldown
+			.set_duration(Duration::from_secs_f32(ENEMY_COOLDOWN * difficulty.enemy_cooldown));
+		enemy
+			.shock_timer
+			.set_duration(Duration::from_secs_f32(SHOCK_DURATION * difficulty.shock_duration));
+
 		shooting.cooldown.tick(time.delta());
 
 		let position = transform.translation.truncate();
@@ -188,7 +220,7 @@ fn update_enemy_ai(
 		let shape_origin = position;
 		let shape_direction = (player_position - position).normalize();
 		let shape_rotation = transform.rotation.z;
-		let max_time_of_impact = ENEMY_SIGHT;
+		let max_time_of_impact = ENEMY_SIGHT * difficulty.perception;
 		let filter = QueryFilter::default()
 			.exclude_collider(entity)
 			.exclude_sensors();
@@ -209,7 +241,7 @@ fn update_enemy_ai(
 					Quat::from_rotation_z(Vec2::Y.angle_between(player_position - position));
 
 				// Don't shoot when off-screen
-				if is_on_screen(position, windows.primary(), camera) {
+				if is_on_screen(position, &viewport, camera, TILE_SIZE / 2.0) {
 					// Don't shoot immediately
 					enemy.shock_timer.tick(time.delta());
 
@@ -224,14 +256,10 @@ fn update_enemy_ai(
 							bullet_texture.clone(),
 							&mut shoot_event,
 							&mut shot_event,
+							rng.gameplay(),
 						);
 
-						AudioPlayer::play_sfx(
-							audio.as_ref(),
-							shot_sound.clone(),
-							ENEMY_SHOT_VOLUME,
-							settings.as_ref(),
-						);
+						sounds.send(PlaySound::new(SoundId::EnemyShot).at(position));
 
 						shooting.cooldown.reset();
 					}
@@ -243,15 +271,7 @@ fn update_enemy_ai(
 			// Not in combat
 			if let EnemyAiState::Combat { player_position } = enemy.ai_state {
 				// When exiting combat
-				let path = nav_mesh
-					.get_nav_mesh()
-					.expect("The nav mesh has not been baked!")
-					.find_path(
-						transform.translation.to_array().into(),
-						player_position.to_array().into(),
-						navmesh::NavQuery::Closest,
-						navmesh::NavPathMode::Accuracy,
-					);
+				let path = find_path(&nav_mesh, position, player_position);
 
 				enemy.ai_state = EnemyAiState::Alert { path, current: 0 };
 			}
@@ -270,6 +290,7 @@ fn shoot(
 	bullet_texture: Handle<Image>,
 	shoot_event: &mut EventWriter<ShootEvent>,
 	shot_event: &mut EventWriter<ShotEvent>,
+	rng: &mut impl Rng,
 ) {
 	let distance = (enemy_transform.translation - player_transform.translation).truncate().length();
 
@@ -278,7 +299,7 @@ fn shoot(
 		let mut bullet_transform = enemy_transform
 			.with_translation(enemy_transform.translation + enemy_transform.up() * TILE_SIZE);
 
-		bullet_transform.rotate_z(random::<f32>() * 0.05);
+		bullet_transform.rotate_z(rng.gen::<f32>() * 0.05);
 
 		let bullet = commands
 			.spawn_bundle(BulletBundle {
@@ -287,14 +308,17 @@ fn shoot(
 					texture: bullet_texture,
 					..Default::default()
 				},
-				bullet: Bullet { speed: 2000.0 },
+				bullet: Bullet {
+					speed: 2000.0,
+					origin: enemy_transform.translation.truncate(),
+				},
 				..Default::default()
 			})
 			.id();
 
 		commands.entity(*tilemap).push_children(&[bullet]);
 	} else {
-		shot_event.send(ShotEvent(*player_entity));
+		shot_event.send(ShotEvent(*player_entity, enemy_transform.translation.truncate()));
 	}
 
 	shoot_event.send(ShootEvent(enemy_transform.translation.truncate()));
@@ -304,21 +328,14 @@ fn alert_on_shot_sound(
 	mut enemies: Query<(&Transform, &mut Enemy)>,
 	mut shot_events: EventReader<ShootEvent>,
 	nav_mesh: Res<EnemyNavMesh>,
+	difficulty: Res<DifficultyScaling>,
 ) {
 	for shot_event in shot_events.iter() {
 		for (enemy_transform, mut enemy) in enemies.iter_mut() {
 			let enemy_position = enemy_transform.translation.truncate();
 
-			if (enemy_position - shot_event.0).length() <= ENEMY_HEARING {
-				let path = nav_mesh
-					.get_nav_mesh()
-					.expect("The nav mesh has not been baked!")
-					.find_path(
-						enemy_position.to_array().into(),
-						shot_event.0.to_array().into(),
-						navmesh::NavQuery::Closest,
-						navmesh::NavPathMode::Accuracy,
-					);
+			if (enemy_position - shot_event.0).length() <= ENEMY_HEARING * difficulty.perception {
+				let path = find_path(&nav_mesh, enemy_position, shot_event.0);
 
 				enemy.ai_state = EnemyAiState::Alert { path, current: 0 };
 			}
@@ -338,8 +355,7 @@ fn update_enemy_position(
 		{
 			let target = path[*current];
 
-			let difference_vector =
-				Vec2::new(target.x, target.y) - transform.translation.truncate();
+			let difference_vector = target - transform.translation.truncate();
 
 			let direction = difference_vector.normalize_or_zero();
 
@@ -388,16 +404,18 @@ fn update_enemy_texture(
 fn get_shot(
 	mut commands: Commands,
 	tilemap_query: Query<Entity, With<Tilemap>>,
-	enemy_query: Query<(Entity, &Transform), With<Enemy>>,
+	enemy_query: Query<(Entity, &Transform, &TilePosition), With<Enemy>>,
 	mut shot_events: EventReader<ShotEvent>,
+	mut death_events: EventWriter<EnemyDeathEvent>,
 	enemy_textures: Res<EnemyTextures>,
-	audio: Res<Audio>,
-	settings: Res<Settings>,
-	screams: Res<Screams>,
+	mut sounds: EventWriter<PlaySound>,
 	mut stats: ResMut<Stats>,
+	mut checkpoints: ResMut<Checkpoints>,
+	mut rng: ResMut<GameRng>,
 ) {
 	let tilemap = tilemap_query.single();
-	let mut enemies: Vec<(Entity, &Transform)> = enemy_query.iter().collect();
+	let rng = rng.cosmetic();
+	let mut enemies: Vec<(Entity, &Transform, &TilePosition)> = enemy_query.iter().collect();
 
 	for shot in shot_events.iter() {
 		let shot_entity = shot.0;
@@ -410,13 +428,15 @@ fn get_shot(
 			let enemy = enemy_tuple.0;
 
 			stats.enemies_killed += 1;
+			checkpoints.enemy_killed(enemy_tuple.2.0);
+			death_events.send(EnemyDeathEvent(enemy_transform.translation.truncate()));
 
 			// Spawn the enemy body
 			let body = commands
 				.spawn_bundle(EnemyBodyBundle {
 					sprite_bundle: SpriteBundle {
-						transform: Transform::from_translation(enemy_transform.translation - Vec3::new(0.0, 0.0, random::<f32>() % 10.0 + 10.0))
-							.with_rotation(Quat::from_rotation_z(rand::random::<f32>() * 2.0 * PI)),
+						transform: Transform::from_translation(enemy_transform.translation - Vec3::new(0.0, 0.0, rng.gen::<f32>() % 10.0 + 10.0))
+							.with_rotation(Quat::from_rotation_z(rng.gen::<f32>() * 2.0 * PI)),
 						texture: enemy_textures.body.clone(),
 						..Default::default()
 					},
@@ -425,22 +445,14 @@ fn get_shot(
 
 			commands.entity(tilemap).push_children(&[body]);
 
-			AudioPlayer::play_sfx(
-				audio.as_ref(),
-				screams
-					.choose(&mut rand::thread_rng())
-					.expect("No scream sounds found.")
-					.clone(),
-				ENEMY_DEATH_SCREAM_VOLUME,
-				settings.as_ref()
-			);
+			sounds.send(PlaySound::new(SoundId::Scream).at(enemy_transform.translation.truncate()));
 
 			// Spawn a few blood splatters
 			let temp: Vec<u32> = (0..4).collect();
 
 			let mut splatters = Vec::new();
 
-			for _ in 0..(*temp.choose(&mut rand::thread_rng()).unwrap()) {
+			for _ in 0..(*temp.choose(rng).unwrap()) {
 				splatters.push(
 					commands
 						.spawn_bundle(EnemyBodyBundle {
@@ -448,13 +460,13 @@ fn get_shot(
 								transform: Transform::from_translation(
 									enemy_transform.translation
 										+ Vec3::new(
-											rand::random::<f32>() * 60.0 - 30.0,
-											rand::random::<f32>() * 60.0 - 30.0,
+											rng.gen::<f32>() * 60.0 - 30.0,
+											rng.gen::<f32>() * 60.0 - 30.0,
 											-10.0,
 										),
 								)
 								.with_rotation(
-									Quat::from_rotation_z(rand::random::<f32>() * 2.0 * PI),
+									Quat::from_rotation_z(rng.gen::<f32>() * 2.0 * PI),
 								),
 								texture: enemy_textures.blood_splatter.clone(),
 								..Default::default()
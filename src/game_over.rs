@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::{GameState, fonts::{PaintFont, RobotoFont}, button::ColoredButton, stats::Stats};
+use crate::{GameState, fonts::{PaintFont, RobotoFont}, button::ColoredButton, stats::Stats, checkpoint::Checkpoints};
 
 #[derive(Component)]
 struct GameOverUi;
@@ -19,6 +19,7 @@ impl Plugin for GameOverPlugin {
 			.add_system_set(
 				SystemSet::on_enter(GameState::GameOver)
 					.with_system(load_ui)
+					.with_system(count_death)
 			)
 			.add_system_set(
 				SystemSet::on_update(GameState::GameOver)
@@ -169,6 +170,10 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 		});
 }
 
+fn count_death(mut stats: ResMut<Stats>) {
+	stats.deaths += 1;
+}
+
 fn drop_ui(mut commands: Commands, ui: Query<Entity, With<GameOverUi>>) {
 	let ui = ui.single();
 	commands.entity(ui).despawn_recursive();
@@ -179,10 +184,12 @@ fn retry_button(
 		&Interaction,
 		(Changed<Interaction>, With<RetryButton>)
 	>,
-	mut state: ResMut<State<GameState>>
+	mut state: ResMut<State<GameState>>,
+	mut checkpoints: ResMut<Checkpoints>,
 ) {
 	for interaction in &mut interaction_query {
 		if *interaction == Interaction::Clicked {
+			checkpoints.request_respawn();
 			state.set(GameState::Game).expect("Failed to change state!");
 		}
 	}
@@ -0,0 +1,326 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::{CheckpointReachedEvent, Checkpoints};
+use crate::player::{Player, SplitTimeUI};
+use crate::replay::Replays;
+use crate::save::PendingLoad;
+use crate::stats::Stats;
+use crate::storage::Storage;
+use crate::tilemap::LEVEL_ID;
+use crate::time::TimeCounter;
+use crate::{GameState, TILE_SIZE};
+
+const GHOSTS_KEY: &str = "ghosts";
+
+/// Seconds of the level timer between the samples of the player's position
+const SAMPLE_INTERVAL: f32 = 0.1;
+
+const GHOST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+/// Right below the player
+const GHOST_Z: f32 = 49.0;
+
+/// Real seconds a split time stays on the screen
+const SPLIT_DURATION: f32 = 3.0;
+const AHEAD_COLOR: Color = Color::rgb(0.2, 0.9, 0.3);
+const BEHIND_COLOR: Color = Color::rgb(0.9, 0.2, 0.2);
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct GhostSample {
+	/// On the level timer
+	time: f32,
+	position: Vec2,
+	rotation: Quat,
+}
+
+/// The path of the player through a run, and when they got to every checkpoint.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct GhostRun {
+	time: f32,
+	samples: Vec<GhostSample>,
+	/// The time on the level timer at each checkpoint, by the tile of the checkpoint
+	splits: HashMap<IVec2, f32>,
+}
+
+impl GhostRun {
+	/// Where the ghost is at the time, in between the samples around it.
+	fn sample(&self, time: f32) -> Option<GhostSample> {
+		let next = self.samples.partition_point(|sample| sample.time <= time);
+
+		if next == 0 || next >= self.samples.len() {
+			return None;
+		}
+
+		let from = self.samples[next - 1];
+		let to = self.samples[next];
+		let t = (time - from.time) / (to.time - from.time);
+
+		Some(GhostSample {
+			time,
+			position: from.position.lerp(to.position, t),
+			rotation: from.rotation.slerp(to.rotation, t),
+		})
+	}
+}
+
+/// The fastest run on every level.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Ghosts {
+	levels: HashMap<String, GhostRun>,
+}
+
+impl FromWorld for Ghosts {
+	fn from_world(world: &mut World) -> Self {
+		let storage = world.get_resource_or_insert_with(Storage::default);
+
+		Self::load(&storage)
+	}
+}
+
+impl Ghosts {
+	fn load(storage: &Storage) -> Self {
+		let ghosts = match storage.read(GHOSTS_KEY) {
+			Some(ghosts) => ghosts,
+			None => return Self::default(),
+		};
+
+		ron::from_str(&ghosts).unwrap_or_else(|error| {
+			warn!("Failed to read the ghosts: {}", error);
+			Self::default()
+		})
+	}
+
+	fn write(&self, storage: &Storage) {
+		let ghosts = match ron::to_string(self) {
+			Ok(ghosts) => ghosts,
+			Err(error) => {
+				error!("Failed to serialize the ghosts: {}", error);
+				return;
+			}
+		};
+
+		if let Err(error) = storage.write(GHOSTS_KEY, &ghosts) {
+			error!("Failed to write the ghosts: {}", error);
+		}
+	}
+}
+
+/// The run being recorded, None if it didn't start from the beginning of the level.
+#[derive(Default)]
+struct GhostRecorder(Option<GhostRun>);
+
+#[derive(Component)]
+struct Ghost;
+
+struct SplitDisplay(Timer);
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<Ghosts>()
+			.init_resource::<GhostRecorder>()
+			.insert_resource(SplitDisplay(Timer::from_seconds(SPLIT_DURATION, false)))
+			.add_system_set(
+				SystemSet::on_enter(GameState::Game).with_system(start_recording.before("begin_attempt")),
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(spawn_ghost)
+					.with_system(record_ghost)
+					.with_system(update_ghost)
+					.with_system(compare_splits.after("reach_checkpoint"))
+					.with_system(hide_split_time),
+			)
+			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(drop_ghost))
+			.add_system_set(SystemSet::on_enter(GameState::Win).with_system(keep_fastest_run));
+	}
+}
+
+fn start_recording(
+	mut recorder: ResMut<GhostRecorder>,
+	mut split_display: ResMut<SplitDisplay>,
+	checkpoints: Res<Checkpoints>,
+	pending_load: Res<PendingLoad>,
+) {
+	split_display.0.reset();
+
+	// Respawning keeps going with the same run, from the checkpoint the player respawns at.
+	// That's the last one they reached, so its split is the latest one.
+	if checkpoints.is_respawning() {
+		if let Some(run) = &mut recorder.0 {
+			let checkpoint_time = run.splits.values().copied().fold(0.0, f32::max);
+
+			run.samples.retain(|sample| sample.time <= checkpoint_time);
+		}
+
+		return;
+	}
+
+	recorder.0 = if pending_load.0.is_some() {
+		None
+	} else {
+		Some(GhostRun::default())
+	};
+}
+
+/// Spawns the ghost along with the player, looking just like them.
+fn spawn_ghost(
+	mut commands: Commands,
+	player_query: Query<&Handle<Image>, Added<Player>>,
+	ghosts: Res<Ghosts>,
+) {
+	let texture = match player_query.get_single() {
+		Ok(texture) => texture,
+		Err(_) => return,
+	};
+
+	if !ghosts.levels.contains_key(LEVEL_ID) {
+		return;
+	}
+
+	commands
+		.spawn_bundle(SpriteBundle {
+			sprite: Sprite {
+				color: GHOST_COLOR,
+				custom_size: Some(Vec2::splat(TILE_SIZE)),
+				..Default::default()
+			},
+			texture: texture.clone(),
+			visibility: Visibility { is_visible: false },
+			..Default::default()
+		})
+		.insert(Name::new("Ghost"))
+		.insert(Ghost);
+}
+
+fn record_ghost(
+	player_query: Query<&Transform, With<Player>>,
+	mut recorder: ResMut<GhostRecorder>,
+	stats: Res<Stats>,
+) {
+	let run = match &mut recorder.0 {
+		Some(run) => run,
+		None => return,
+	};
+
+	let time = stats.timer.elapsed_secs();
+
+	if run.samples.last().map_or(false, |last| time < last.time + SAMPLE_INTERVAL) {
+		return;
+	}
+
+	let player_transform = player_query.single();
+
+	run.samples.push(GhostSample {
+		time,
+		position: player_transform.translation.truncate(),
+		rotation: player_transform.rotation,
+	});
+}
+
+/// Moves the ghost to where the fastest run was at this point of the level timer, and hides it once it's done.
+fn update_ghost(
+	mut ghost_query: Query<(&mut Transform, &mut Visibility), With<Ghost>>,
+	ghosts: Res<Ghosts>,
+	stats: Res<Stats>,
+) {
+	let (mut transform, mut visibility) = match ghost_query.get_single_mut() {
+		Ok(ghost) => ghost,
+		Err(_) => return,
+	};
+
+	let sample = ghosts
+		.levels
+		.get(LEVEL_ID)
+		.and_then(|run| run.sample(stats.timer.elapsed_secs()));
+
+	match sample {
+		Some(sample) => {
+			transform.translation = sample.position.extend(GHOST_Z);
+			transform.rotation = sample.rotation;
+			visibility.is_visible = true;
+		}
+		None => visibility.is_visible = false,
+	}
+}
+
+/// Records the time at each checkpoint and shows how far ahead or behind the ghost it is.
+fn compare_splits(
+	mut reached_events: EventReader<CheckpointReachedEvent>,
+	mut split_query: Query<&mut Text, With<SplitTimeUI>>,
+	mut recorder: ResMut<GhostRecorder>,
+	mut split_display: ResMut<SplitDisplay>,
+	ghosts: Res<Ghosts>,
+	stats: Res<Stats>,
+) {
+	let time = stats.timer.elapsed_secs();
+
+	for reached in reached_events.iter() {
+		if let Some(run) = &mut recorder.0 {
+			run.splits.insert(reached.0, time);
+		}
+
+		let ghost_time = match ghosts.levels.get(LEVEL_ID).and_then(|run| run.splits.get(&reached.0)) {
+			Some(ghost_time) => *ghost_time,
+			None => continue,
+		};
+
+		let difference = time - ghost_time;
+		let mut text = split_query.single_mut();
+
+		text.sections[0].value = format!("{:+.2}", difference);
+		text.sections[0].style.color = if difference <= 0.0 { AHEAD_COLOR } else { BEHIND_COLOR };
+
+		split_display.0.reset();
+	}
+}
+
+fn hide_split_time(
+	mut split_query: Query<&mut Text, With<SplitTimeUI>>,
+	mut split_display: ResMut<SplitDisplay>,
+	time: Res<TimeCounter>,
+) {
+	split_display.0.tick(time.real_delta());
+
+	if split_display.0.just_finished() {
+		for mut text in split_query.iter_mut() {
+			text.sections[0].value.clear();
+		}
+	}
+}
+
+fn drop_ghost(mut commands: Commands, ghost_query: Query<Entity, With<Ghost>>) {
+	for ghost in ghost_query.iter() {
+		commands.entity(ghost).despawn_recursive();
+	}
+}
+
+/// Keeps the run as the ghost of the level if it's the fastest one yet.
+fn keep_fastest_run(
+	mut ghosts: ResMut<Ghosts>,
+	mut recorder: ResMut<GhostRecorder>,
+	stats: Res<Stats>,
+	replays: Res<Replays>,
+	storage: Res<Storage>,
+) {
+	let mut run = match recorder.0.take() {
+		Some(run) => run,
+		None => return,
+	};
+
+	// The replay is the same run that's already been counted
+	if replays.watched() {
+		return;
+	}
+
+	run.time = stats.timer.elapsed_secs();
+
+	if ghosts.levels.get(LEVEL_ID).map_or(false, |best| best.time <= run.time) {
+		return;
+	}
+
+	ghosts.levels.insert(LEVEL_ID.to_string(), run);
+	ghosts.write(&storage);
+}
@@ -0,0 +1,375 @@
+//! Runs the gameplay without a window, a renderer or a real clock, so it can be tested.
+//! Every frame takes exactly `FRAME_TIME`, and the input is set by the test instead of read from the devices.
+
+use bevy::asset::AssetPlugin;
+use bevy::hierarchy::HierarchyPlugin;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::transform::TransformPlugin;
+use bevy::window::WindowPlugin;
+use bevy_kira_audio::AudioPlugin;
+use bevy_rapier2d::prelude::*;
+
+use crate::audio::AudioLoadPlugin;
+use crate::audio_player::AudioPlayerPlugin;
+use crate::bullet::BulletPlugin;
+use crate::camera::{CameraController, CameraPlugin, Viewport};
+use crate::checkpoint::CheckpointPlugin;
+use crate::crafting::CraftingPlugin;
+use crate::difficulty::DifficultyPlugin;
+use crate::enemy::EnemyPlugin;
+use crate::fonts::FontPlugin;
+use crate::highscores::HighScoresPlugin;
+use crate::item::ItemPlugin;
+use crate::pickup::PickupPlugin;
+use crate::player::PlayerPlugin;
+use crate::player_input::{PlayerInput, PlayerInputPlugin};
+use crate::post_processing::MainCamera;
+use crate::replay::{ReplayPlugin, Replays};
+use crate::rng::{GameRng, RngPlugin};
+use crate::save::SavePlugin;
+use crate::settings::{Settings, SettingsPlugin};
+use crate::shader_params::ShaderParamsPlugin;
+use crate::stats::StatsPlugin;
+use crate::storage::Storage;
+use crate::tilemap::{LevelSource, TileMapPlugin};
+use crate::time::TimeCounter;
+use crate::win::WinMaterial;
+use crate::GameState;
+
+pub const FRAME_TIME: f32 = 1.0 / 60.0;
+
+/// Every simulation starts from the same seed, so the tests always play out the same way
+const SEED: u64 = 0;
+
+/// The input the test wants the player to give on the next frames.
+#[derive(Default)]
+struct ScriptedInput {
+	input: PlayerInput,
+	/// A point in the world the cursor keeps pointing at, wherever the camera goes
+	aim: Option<Vec2>,
+}
+
+pub struct Simulation {
+	app: App,
+}
+
+impl Simulation {
+	/// Builds the gameplay plugins around the given level file.
+	/// Has to be called from the thread of the test, its name picks where the simulation keeps its saves.
+	pub fn new(level: &'static [u8]) -> Self {
+		let test_name = std::thread::current().name().unwrap_or("simulation").to_string();
+		let mut app = App::new();
+
+		app.add_plugins(MinimalPlugins)
+			.add_plugin(AssetPlugin)
+			.add_plugin(WindowPlugin {
+				add_primary_window: false,
+				exit_on_all_closed: false,
+				..Default::default()
+			})
+			.add_plugin(InputPlugin)
+			.add_plugin(TransformPlugin)
+			.add_plugin(HierarchyPlugin)
+			.add_plugin(AudioPlugin)
+			.add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+			.add_asset::<WinMaterial>()
+			.add_state(GameState::MainMenu)
+			.insert_resource(TimeCounter::new())
+			.insert_resource(LevelSource(level))
+			.insert_resource(Storage::for_test(&test_name))
+			.init_resource::<ScriptedInput>()
+			.add_startup_system(spawn_camera)
+			.add_system_to_stage(CoreStage::PreUpdate, fixed_time.label("update_time"))
+			.add_system_to_stage(
+				CoreStage::PreUpdate,
+				apply_scripted_input.after("sample_input"),
+			)
+			.add_plugin(AudioLoadPlugin)
+			.add_plugin(AudioPlayerPlugin)
+			.add_plugin(FontPlugin)
+			.add_plugin(TileMapPlugin)
+			.add_plugin(PlayerPlugin)
+			.add_plugin(EnemyPlugin)
+			.add_plugin(BulletPlugin)
+			.add_plugin(StatsPlugin)
+			.add_plugin(SettingsPlugin)
+			.add_plugin(CheckpointPlugin)
+			.add_plugin(SavePlugin)
+			.add_plugin(HighScoresPlugin)
+			.add_plugin(ItemPlugin)
+			.add_plugin(CraftingPlugin)
+			.add_plugin(PickupPlugin)
+			.add_plugin(CameraPlugin)
+			.add_plugin(ShaderParamsPlugin)
+			.add_plugin(DifficultyPlugin)
+			.add_plugin(RngPlugin)
+			.add_plugin(PlayerInputPlugin)
+			.add_plugin(ReplayPlugin);
+
+		// The physics take the same step as the rest of the game, whatever the real clock says
+		app.world.resource_mut::<RapierConfiguration>().timestep_mode = TimestepMode::Fixed {
+			dt: FRAME_TIME,
+			substeps: 1,
+		};
+
+		Self { app }
+	}
+
+	/// Starts a run of the level, the player is there once this returns.
+	pub fn start(&mut self) {
+		self.app.world.resource_mut::<GameRng>().set_next_seed(SEED);
+
+		self.app
+			.world
+			.resource_mut::<State<GameState>>()
+			.set(GameState::Game)
+			.expect("The simulation was already started");
+
+		self.step(1);
+	}
+
+	/// Plays back the last run that was won, from the main menu.
+	pub fn watch_replay(&mut self) {
+		let world = &mut self.app.world;
+
+		world.resource_scope(|world, mut replays: Mut<Replays>| {
+			world.resource_scope(|world, mut settings: Mut<Settings>| {
+				world.resource_scope(|world, mut rng: Mut<GameRng>| {
+					replays.watch(&mut settings, &mut rng, world.resource::<Viewport>());
+				});
+			});
+		});
+
+		world
+			.resource_mut::<State<GameState>>()
+			.set(GameState::Game)
+			.expect("The simulation was already in a run");
+
+		self.step(1);
+	}
+
+	pub fn step(&mut self, frames: usize) {
+		for _ in 0..frames {
+			self.app.update();
+			self.app.world.resource_mut::<ScriptedInput>().input.end_frame();
+		}
+	}
+
+	/// Holds the key down for the rest of the simulation.
+	pub fn press(&mut self, key: char) {
+		self.app.world.resource_mut::<ScriptedInput>().input.press(key);
+	}
+
+	/// Points the cursor at a point in the world.
+	pub fn aim_at(&mut self, target: Vec2) {
+		self.app.world.resource_mut::<ScriptedInput>().aim = Some(target);
+	}
+
+	/// Pulls the trigger on the next frame.
+	pub fn shoot(&mut self) {
+		self.app.world.resource_mut::<ScriptedInput>().input.set_shoot(true);
+	}
+
+	pub fn state(&self) -> GameState {
+		*self.app.world.resource::<State<GameState>>().current()
+	}
+
+	pub fn world(&mut self) -> &mut World {
+		&mut self.app.world
+	}
+}
+
+/// The game only needs the transform of the camera, rendering is left out.
+fn spawn_camera(mut commands: Commands) {
+	commands
+		.spawn_bundle(TransformBundle::default())
+		.insert(MainCamera)
+		.insert(CameraController::default())
+		.insert(Name::new("MainCamera"));
+}
+
+fn fixed_time(mut time: ResMut<TimeCounter>) {
+	time.step(FRAME_TIME);
+}
+
+fn apply_scripted_input(
+	mut script: ResMut<ScriptedInput>,
+	mut input: ResMut<PlayerInput>,
+	camera_query: Query<&Transform, With<MainCamera>>,
+	viewport: Res<Viewport>,
+	replays: Res<Replays>,
+) {
+	// The replay gives the input
+	if replays.is_playing() {
+		return;
+	}
+
+	if let Some(target) = script.aim {
+		let camera_position = camera_query.single().translation.truncate();
+
+		script
+			.input
+			.set_cursor(Some(target - camera_position + viewport.size / 2.0));
+	}
+
+	*input = script.input.clone();
+}
+
+mod tests {
+	use super::*;
+	use crate::enemy::Enemy;
+	use crate::item::SHELLS;
+	use crate::player::Player;
+	use crate::replay::ReplayResult;
+	use crate::stats::Stats;
+	use crate::unit::Inventory;
+	use crate::TILE_SIZE;
+
+	const TEST_LEVEL: &[u8] = include_bytes!("../assets/level/test.tmx");
+
+	/// Frames until the gun can fire after the run starts
+	const WEAPON_READY: usize = 40;
+
+	fn tile_position(x: i32, y: i32) -> Vec2 {
+		Vec2::new(x as f32, -y as f32) * TILE_SIZE
+	}
+
+	fn enemy_count(world: &mut World) -> usize {
+		world.query_filtered::<Entity, With<Enemy>>().iter(world).count()
+	}
+
+	fn step_until_over(simulation: &mut Simulation, max_frames: usize) {
+		for _ in 0..max_frames {
+			if simulation.state() != GameState::Game {
+				break;
+			}
+
+			simulation.step(1);
+		}
+	}
+
+	#[test]
+	fn level_loads() {
+		let mut simulation = Simulation::new(TEST_LEVEL);
+		simulation.start();
+
+		let world = simulation.world();
+		let player = world
+			.query_filtered::<&Transform, With<Player>>()
+			.single(world)
+			.translation
+			.truncate();
+
+		assert_eq!(player, tile_position(3, 11));
+		assert_eq!(enemy_count(world), 1);
+		assert_eq!(simulation.state(), GameState::Game);
+	}
+
+	#[test]
+	fn enemy_dies_when_shot() {
+		let mut simulation = Simulation::new(TEST_LEVEL);
+		simulation.start();
+
+		simulation.aim_at(tile_position(3, 6));
+		simulation.step(WEAPON_READY);
+
+		simulation.shoot();
+		simulation.step(30);
+
+		assert_eq!(enemy_count(simulation.world()), 0);
+		assert_eq!(simulation.world().resource::<Stats>().enemies_killed, 1);
+	}
+
+	#[test]
+	fn missing_leaves_the_enemy_alive() {
+		let mut simulation = Simulation::new(TEST_LEVEL);
+		simulation.start();
+
+		// Straight at the wall on the right
+		simulation.aim_at(tile_position(15, 11));
+		simulation.step(WEAPON_READY);
+
+		simulation.shoot();
+		simulation.step(30);
+
+		assert_eq!(enemy_count(simulation.world()), 1);
+		assert_eq!(simulation.world().resource::<Stats>().shot_fired, 1);
+	}
+
+	#[test]
+	fn empty_gun_doesnt_fire() {
+		let mut simulation = Simulation::new(TEST_LEVEL);
+		simulation.start();
+
+		let world = simulation.world();
+		let mut inventory = world.query_filtered::<&mut Inventory, With<Player>>().single_mut(world);
+		let shells = inventory.quantity(SHELLS);
+		inventory.remove(SHELLS, shells);
+
+		simulation.aim_at(tile_position(3, 6));
+		simulation.step(WEAPON_READY);
+
+		simulation.shoot();
+		simulation.step(30);
+
+		assert_eq!(enemy_count(simulation.world()), 1);
+		assert_eq!(simulation.world().resource::<Stats>().shot_fired, 0);
+	}
+
+	#[test]
+	fn touching_the_win_tile_wins() {
+		let mut simulation = Simulation::new(TEST_LEVEL);
+		simulation.start();
+
+		simulation.press('D');
+
+		for _ in 0..120 {
+			if simulation.state() != GameState::Game {
+				break;
+			}
+
+			simulation.step(1);
+		}
+
+		assert_eq!(simulation.state(), GameState::Win);
+	}
+
+	#[test]
+	fn replay_ends_like_the_recorded_run() {
+		let mut simulation = Simulation::new(TEST_LEVEL);
+		simulation.start();
+
+		simulation.aim_at(tile_position(3, 6));
+		simulation.step(WEAPON_READY);
+
+		simulation.shoot();
+		simulation.step(30);
+
+		simulation.press('D');
+		step_until_over(&mut simulation, 120);
+
+		assert_eq!(simulation.state(), GameState::Win);
+
+		let world = simulation.world();
+		world
+			.resource_mut::<State<GameState>>()
+			.set(GameState::MainMenu)
+			.unwrap();
+
+		simulation.step(1);
+
+		// Read it back from the storage, like when the game starts again
+		let world = simulation.world();
+		let replays = Replays::from_world(world);
+		assert!(replays.can_watch());
+		world.insert_resource(replays);
+
+		simulation.watch_replay();
+		step_until_over(&mut simulation, 600);
+
+		assert_eq!(simulation.state(), GameState::Win);
+		assert_eq!(simulation.world().resource::<Replays>().result(), Some(ReplayResult::InSync));
+		assert_eq!(simulation.world().resource::<Stats>().enemies_killed, 1);
+	}
+}
@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::{Difficulty, RunDifficulty};
+use crate::replay::Replays;
+use crate::stats::Stats;
+use crate::storage::Storage;
+use crate::tilemap::LEVEL_ID;
+use crate::GameState;
+
+const HIGHSCORES_KEY: &str = "highscores";
+
+pub const RECORD_COLOR: Color = Color::GOLD;
+
+pub struct HighScoresPlugin;
+
+impl Plugin for HighScoresPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<HighScores>()
+			.insert_resource(NewRecords::default())
+			.add_system_set(
+				SystemSet::on_enter(GameState::Win).with_system(record_run.label("record_run")),
+			);
+	}
+}
+
+/// A record and the difficulty it was set on.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Record<T> {
+	pub value: T,
+	pub difficulty: Difficulty,
+}
+
+impl<T> Record<T> {
+	/// Shown after the value, records set on normal don't mention it.
+	pub fn difficulty_suffix(&self) -> String {
+		match self.difficulty {
+			Difficulty::Normal => String::new(),
+			difficulty => format!(" ({})", difficulty.name()),
+		}
+	}
+}
+
+/// The best results ever achieved on a level.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct LevelRecords {
+	pub best_time: Option<Record<f32>>,
+	pub fewest_shots: Option<Record<u16>>,
+	pub most_kills: Option<Record<u16>>,
+	pub no_damage_runs: u32,
+}
+
+/// Which records the last finished run has broken.
+#[derive(Default, Debug)]
+pub struct NewRecords {
+	pub best_time: bool,
+	pub fewest_shots: bool,
+	pub most_kills: bool,
+	pub no_damage: bool,
+}
+
+impl NewRecords {
+	pub fn any(&self) -> bool {
+		self.best_time || self.fewest_shots || self.most_kills || self.no_damage
+	}
+}
+
+/// The records of every level, kept apart for every difficulty so an easier run can't beat a harder one.
+#[derive(Serialize, Deserialize, Default)]
+pub struct HighScores {
+	records: HashMap<String, HashMap<Difficulty, LevelRecords>>,
+}
+
+impl FromWorld for HighScores {
+	fn from_world(world: &mut World) -> Self {
+		let storage = world.get_resource_or_insert_with(Storage::default);
+
+		Self::load(&storage)
+	}
+}
+
+impl HighScores {
+	fn load(storage: &Storage) -> Self {
+		let highscores = match storage.read(HIGHSCORES_KEY) {
+			Some(highscores) => highscores,
+			None => return Self::default(),
+		};
+
+		ron::from_str(&highscores).unwrap_or_else(|error| {
+			warn!("Failed to read the high scores: {}", error);
+			Self::default()
+		})
+	}
+
+	fn write(&self, storage: &Storage) {
+		let highscores = match ron::to_string(self) {
+			Ok(highscores) => highscores,
+			Err(error) => {
+				error!("Failed to serialize the high scores: {}", error);
+				return;
+			}
+		};
+
+		if let Err(error) = storage.write(HIGHSCORES_KEY, &highscores) {
+			error!("Failed to write the high scores: {}", error);
+		}
+	}
+
+	pub fn level(&self, level: &str, difficulty: Difficulty) -> Option<&LevelRecords> {
+		self.records.get(level)?.get(&difficulty)
+	}
+
+	/// Updates the records of a level on the difficulty of a finished run.
+	///
+	/// # Returns
+	/// Which records the run has broken.
+	pub fn submit(&mut self, level: &str, stats: &Stats, difficulty: Difficulty) -> NewRecords {
+		let records = self
+			.records
+			.entry(level.to_string())
+			.or_default()
+			.entry(difficulty)
+			.or_default();
+		let mut new_records = NewRecords::default();
+
+		let time = stats.timer.elapsed_secs();
+
+		if records.best_time.map_or(true, |best_time| time < best_time.value) {
+			records.best_time = Some(Record { value: time, difficulty });
+			new_records.best_time = true;
+		}
+
+		if records.fewest_shots.map_or(true, |fewest_shots| stats.shot_fired < fewest_shots.value) {
+			records.fewest_shots = Some(Record {
+				value: stats.shot_fired,
+				difficulty,
+			});
+			new_records.fewest_shots = true;
+		}
+
+		if records.most_kills.map_or(true, |most_kills| stats.enemies_killed > most_kills.value) {
+			records.most_kills = Some(Record {
+				value: stats.enemies_killed,
+				difficulty,
+			});
+			new_records.most_kills = true;
+		}
+
+		if stats.damage_taken <= 0.0 {
+			records.no_damage_runs += 1;
+			new_records.no_damage = true;
+		}
+
+		new_records
+	}
+}
+
+fn record_run(
+	mut highscores: ResMut<HighScores>,
+	mut new_records: ResMut<NewRecords>,
+	stats: Res<Stats>,
+	run_difficulty: Res<RunDifficulty>,
+	replays: Res<Replays>,
+	storage: Res<Storage>,
+) {
+	// Watching a replay doesn't set any records
+	if replays.watched() {
+		*new_records = NewRecords::default();
+		return;
+	}
+
+	*new_records = highscores.submit(LEVEL_ID, &stats, **run_difficulty);
+
+	highscores.write(&storage);
+}
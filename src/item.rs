@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Ids of the items the game's code refers to directly
+pub const COCAINE: &str = "cocaine";
+pub const SHELLS: &str = "shells";
+
+/// What happens when the player uses an item.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum UseAction {
+	/// Consume the item and take a dose of the effect with the given id
+	Effect(String),
+	/// Consume the item and heal the given amount, can't be used at full health
+	Heal(f32),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemDefinition {
+	pub id: String,
+	pub name: String,
+	/// Path to the icon, relative to the assets folder
+	pub icon: String,
+	pub max_stack: u32,
+	/// The letter or digit of the key that uses the item
+	#[serde(default)]
+	pub hotkey: Option<char>,
+	#[serde(default)]
+	pub use_action: Option<UseAction>,
+}
+
+pub fn key_code(key: char) -> Option<KeyCode> {
+	let key_code = match key.to_ascii_uppercase() {
+		'A' => KeyCode::A,
+		'B' => KeyCode::B,
+		'C' => KeyCode::C,
+		'D' => KeyCode::D,
+		'E' => KeyCode::E,
+		'F' => KeyCode::F,
+		'G' => KeyCode::G,
+		'H' => KeyCode::H,
+		'I' => KeyCode::I,
+		'J' => KeyCode::J,
+		'K' => KeyCode::K,
+		'L' => KeyCode::L,
+		'M' => KeyCode::M,
+		'N' => KeyCode::N,
+		'O' => KeyCode::O,
+		'P' => KeyCode::P,
+		'Q' => KeyCode::Q,
+		'R' => KeyCode::R,
+		'S' => KeyCode::S,
+		'T' => KeyCode::T,
+		'U' => KeyCode::U,
+		'V' => KeyCode::V,
+		'W' => KeyCode::W,
+		'X' => KeyCode::X,
+		'Y' => KeyCode::Y,
+		'Z' => KeyCode::Z,
+		'0' => KeyCode::Key0,
+		'1' => KeyCode::Key1,
+		'2' => KeyCode::Key2,
+		'3' => KeyCode::Key3,
+		'4' => KeyCode::Key4,
+		'5' => KeyCode::Key5,
+		'6' => KeyCode::Key6,
+		'7' => KeyCode::Key7,
+		'8' => KeyCode::Key8,
+		'9' => KeyCode::Key9,
+		_ => return None,
+	};
+
+	Some(key_code)
+}
+
+/// All the items defined in `assets/items/items.ron`, in the order they're defined in.
+pub struct ItemRegistry {
+	items: Vec<ItemDefinition>,
+	indices: HashMap<String, usize>,
+}
+
+impl ItemRegistry {
+	fn load() -> Self {
+		// Included in the binary like the level, so it works on the web as well
+		let items: Vec<ItemDefinition> =
+			ron::from_str(include_str!("../assets/items/items.ron")).expect("Invalid item definitions!");
+
+		let indices = items
+			.iter()
+			.enumerate()
+			.map(|(index, item)| (item.id.clone(), index))
+			.collect();
+
+		Self { items, indices }
+	}
+
+	pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+		self.indices.get(id).map(|index| &self.items[*index])
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &ItemDefinition> {
+		self.items.iter()
+	}
+}
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(ItemRegistry::load());
+	}
+}
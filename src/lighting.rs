@@ -0,0 +1,339 @@
+use bevy::{
+	prelude::*,
+	reflect::TypeUuid,
+	render::render_resource::{AsBindGroup, Extent3d, ShaderRef, ShaderType, TextureDimension, TextureFormat},
+	sprite::Material2d,
+};
+use tiled::{Properties, PropertyValue};
+
+use crate::camera::Viewport;
+use crate::post_processing::{
+	spawn_pass, CameraRenderImage, MainCamera, PassLayers, PostProcessingMaterial, PostProcessingMaterialPlugin,
+	PostProcessingPass, ScreenQuad,
+};
+use crate::tilemap::{LevelBounds, TileCollider};
+use crate::time::TimeCounter;
+use crate::unit::ShootEvent;
+use crate::{GameState, TILE_SIZE};
+
+/// Has to match the size of the array in `lighting.wgsl`
+const MAX_LIGHTS: usize = 16;
+
+/// The light everything gets even if there's no lamp nearby
+const AMBIENT_LIGHT: Color = Color::rgb(0.3, 0.3, 0.4);
+
+const LAMP_COLOR: Color = Color::rgb(1.0, 0.85, 0.6);
+
+const MUZZLE_FLASH_COLOR: Color = Color::rgb(1.0, 0.75, 0.4);
+const MUZZLE_FLASH_RADIUS: f32 = TILE_SIZE * 4.0;
+const MUZZLE_FLASH_DURATION: f32 = 0.08;
+
+/// Lights the world before any of the other passes change how it looks
+const LIGHTING_ORDER: u32 = 5;
+
+#[derive(Component, Clone)]
+pub struct PointLight2d {
+	pub color: Color,
+	pub radius: f32,
+	pub intensity: f32,
+}
+
+impl PointLight2d {
+	/// Reads a lamp from the properties of a tile in the tileset,
+	/// `light_radius` in tiles and an optional `light_intensity`.
+	pub fn from_properties(properties: &Properties) -> Option<Self> {
+		let radius = match properties.get("light_radius") {
+			Some(PropertyValue::FloatValue(radius)) => *radius,
+			Some(PropertyValue::IntValue(radius)) => *radius as f32,
+			_ => return None,
+		};
+
+		let intensity = match properties.get("light_intensity") {
+			Some(PropertyValue::FloatValue(intensity)) => *intensity,
+			_ => 1.0,
+		};
+
+		Some(Self {
+			color: LAMP_COLOR,
+			radius: radius * TILE_SIZE,
+			intensity,
+		})
+	}
+}
+
+/// A light that fades out and disappears, like a muzzle flash.
+#[derive(Component)]
+struct LightFlash {
+	timer: Timer,
+	intensity: f32,
+}
+
+#[derive(Component)]
+struct LightingPass;
+
+#[derive(Clone, Copy, Default, ShaderType)]
+pub struct GpuLight {
+	position_radius: Vec4,
+	color_intensity: Vec4,
+}
+
+#[derive(Clone, Default, ShaderType)]
+pub struct LightingUniform {
+	ambient: Vec4,
+	occlusion_area: Vec4,
+	info: Vec4,
+	view_area: Vec4,
+	lights: [GpuLight; MAX_LIGHTS],
+}
+
+/// Multiplies the image of the world with the light that reaches every point of it.
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "c7a3e0f4-5b1d-4a8e-9f26-3d8b7e41a5c2"]
+pub struct LightingMaterial {
+	/// The image rendered by the previous pass
+	#[texture(0)]
+	#[sampler(1)]
+	pub source_image: Handle<Image>,
+	#[uniform(2)]
+	pub lighting: LightingUniform,
+	#[texture(3)]
+	pub occlusion_map: Handle<Image>,
+}
+
+impl Material2d for LightingMaterial {
+	fn fragment_shader() -> ShaderRef {
+		"shaders/lighting.wgsl".into()
+	}
+}
+
+impl PostProcessingMaterial for LightingMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
+
+/// Where the walls are, one texel per tile, so the shader can cast shadows.
+struct OcclusionMap {
+	image: Handle<Image>,
+	/// World position of the bottom left corner of the map
+	origin: Vec2,
+	size: UVec2,
+}
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_plugin(PostProcessingMaterialPlugin::<LightingMaterial>::default())
+			.add_startup_system(setup_occlusion_map)
+			.add_startup_system_to_stage(StartupStage::PostStartup, spawn_lighting_pass)
+			.add_system_set(SystemSet::on_enter(GameState::Game).with_system(enable_lighting))
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(build_occlusion_map)
+					.with_system(spawn_muzzle_flashes)
+					.with_system(update_light_flashes)
+					.with_system(update_lighting.after("camera_follow_player")),
+			)
+			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(drop_lighting));
+	}
+}
+
+fn occlusion_image(size: UVec2, data: Vec<u8>) -> Image {
+	Image::new(
+		Extent3d {
+			width: size.x,
+			height: size.y,
+			depth_or_array_layers: 1,
+		},
+		TextureDimension::D2,
+		data,
+		TextureFormat::R8Unorm,
+	)
+}
+
+fn setup_occlusion_map(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+	// Empty until the level gets loaded
+	let image = images.add(occlusion_image(UVec2::ONE, vec![0]));
+
+	commands.insert_resource(OcclusionMap {
+		image,
+		origin: Vec2::ZERO,
+		size: UVec2::ONE,
+	});
+}
+
+fn spawn_lighting_pass(
+	mut commands: Commands,
+	mut layers: ResMut<PassLayers>,
+	screen_quad: Res<ScreenQuad>,
+	source_image: Res<CameraRenderImage>,
+	mut materials: ResMut<Assets<LightingMaterial>>,
+	occlusion_map: Res<OcclusionMap>,
+) {
+	let material = materials.add(LightingMaterial {
+		source_image: source_image.0.clone(),
+		lighting: LightingUniform::default(),
+		occlusion_map: occlusion_map.image.clone(),
+	});
+
+	let pass = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		material,
+		PostProcessingPass {
+			order: LIGHTING_ORDER,
+			enabled: false,
+		},
+		"LightingPass",
+	);
+
+	commands.entity(pass).insert(LightingPass);
+}
+
+/// Only the levels are lit, the menus are drawn as they are.
+fn enable_lighting(mut pass_query: Query<&mut PostProcessingPass, With<LightingPass>>) {
+	for mut pass in pass_query.iter_mut() {
+		pass.enabled = true;
+	}
+}
+
+/// Rebuilds the occlusion map once the walls of a new level get spawned.
+fn build_occlusion_map(
+	new_walls: Query<(), Added<TileCollider>>,
+	walls: Query<&Transform, With<TileCollider>>,
+	bounds: Option<Res<LevelBounds>>,
+	mut occlusion_map: ResMut<OcclusionMap>,
+	mut images: ResMut<Assets<Image>>,
+) {
+	let bounds = match bounds {
+		Some(bounds) if !new_walls.is_empty() => bounds,
+		_ => return,
+	};
+
+	let size = ((bounds.max - bounds.min) / TILE_SIZE).round().max(Vec2::ONE).as_uvec2();
+	let mut data = vec![0; (size.x * size.y) as usize];
+
+	for transform in walls.iter() {
+		let tile = ((transform.translation.truncate() - bounds.min) / TILE_SIZE).floor();
+
+		if tile.x < 0.0 || tile.y < 0.0 || tile.x >= size.x as f32 || tile.y >= size.y as f32 {
+			continue;
+		}
+
+		data[(tile.y as u32 * size.x + tile.x as u32) as usize] = 255;
+	}
+
+	// The materials keep the same handle, so only the image gets replaced
+	if let Some(image) = images.get_mut(&occlusion_map.image) {
+		*image = occlusion_image(size, data);
+	}
+
+	occlusion_map.origin = bounds.min;
+	occlusion_map.size = size;
+}
+
+fn spawn_muzzle_flashes(mut commands: Commands, mut shoot_events: EventReader<ShootEvent>) {
+	for shoot in shoot_events.iter() {
+		commands
+			.spawn_bundle(TransformBundle::from_transform(Transform::from_translation(shoot.0.extend(0.0))))
+			.insert(PointLight2d {
+				color: MUZZLE_FLASH_COLOR,
+				radius: MUZZLE_FLASH_RADIUS,
+				intensity: 1.0,
+			})
+			.insert(LightFlash {
+				timer: Timer::from_seconds(MUZZLE_FLASH_DURATION, false),
+				intensity: 1.0,
+			})
+			.insert(Name::new("MuzzleFlash"));
+	}
+}
+
+fn update_light_flashes(
+	mut commands: Commands,
+	mut flash_query: Query<(Entity, &mut PointLight2d, &mut LightFlash)>,
+	time: Res<TimeCounter>,
+) {
+	for (entity, mut light, mut flash) in flash_query.iter_mut() {
+		flash.timer.tick(time.delta());
+
+		if flash.timer.finished() {
+			commands.entity(entity).despawn_recursive();
+			continue;
+		}
+
+		light.intensity = flash.intensity * (1.0 - flash.timer.percent());
+	}
+}
+
+/// Sends the part of the world on the screen and the closest lights on it to the shader.
+fn update_lighting(
+	pass_query: Query<&Handle<LightingMaterial>, With<LightingPass>>,
+	camera_query: Query<&Transform, With<MainCamera>>,
+	light_query: Query<(&Transform, &PointLight2d)>,
+	occlusion_map: Res<OcclusionMap>,
+	mut materials: ResMut<Assets<LightingMaterial>>,
+	viewport: Res<Viewport>,
+) {
+	let material = match pass_query.get_single() {
+		Ok(material) => material,
+		Err(_) => return,
+	};
+	let camera_transform = camera_query.single();
+
+	let camera_position = camera_transform.translation.truncate();
+	let half_size = viewport.size / 2.0;
+
+	let mut lights: Vec<(f32, Vec2, &PointLight2d)> = light_query
+		.iter()
+		.map(|(transform, light)| (transform.translation.truncate(), light))
+		.filter(|(position, light)| {
+			let offset = (*position - camera_position).abs();
+
+			offset.x < half_size.x + light.radius && offset.y < half_size.y + light.radius
+		})
+		.map(|(position, light)| ((position - camera_position).length_squared(), position, light))
+		.collect();
+
+	lights.sort_by(|(distance, ..), (other_distance, ..)| distance.total_cmp(other_distance));
+	lights.truncate(MAX_LIGHTS);
+
+	let material = match materials.get_mut(material) {
+		Some(material) => material,
+		None => return,
+	};
+
+	let uniform = &mut material.lighting;
+
+	uniform.ambient = Vec4::from(AMBIENT_LIGHT.as_rgba_f32());
+	uniform.occlusion_area = occlusion_map
+		.origin
+		.extend(occlusion_map.size.x as f32)
+		.extend(occlusion_map.size.y as f32);
+	uniform.info = Vec4::new(lights.len() as f32, TILE_SIZE, 0.0, 0.0);
+	uniform.view_area = camera_position.extend(viewport.size.x).extend(viewport.size.y);
+
+	for (gpu_light, (_, position, light)) in uniform.lights.iter_mut().zip(&lights) {
+		let color = light.color.as_rgba_f32();
+
+		gpu_light.position_radius = Vec4::new(position.x, position.y, light.radius, 0.0);
+		gpu_light.color_intensity = Vec4::new(color[0], color[1], color[2], light.intensity);
+	}
+}
+
+fn drop_lighting(
+	mut commands: Commands,
+	mut pass_query: Query<&mut PostProcessingPass, With<LightingPass>>,
+	flash_query: Query<Entity, With<LightFlash>>,
+) {
+	for mut pass in pass_query.iter_mut() {
+		pass.enabled = false;
+	}
+
+	for entity in flash_query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
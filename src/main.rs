@@ -4,6 +4,8 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use bevy_prototype_debug_lines::*;
 use bevy_kira_audio::prelude::*;
+use bevy::window::WindowMode;
+use bevy::asset::AssetServerSettings;
 
 // we dont need those things in wasm build since they are for setting window icon
 #[cfg(not(target_arch="wasm32"))]
@@ -22,7 +24,7 @@ pub const TILE_SIZE: f32 = 50.0;
 mod unit;
 mod player;
 mod enemy;
-mod cocaine;
+mod pickup;
 mod bullet;
 mod time;
 mod debug;
@@ -40,6 +42,23 @@ mod audio_player;
 mod music;
 mod stats;
 mod settings;
+mod pause;
+mod checkpoint;
+mod storage;
+mod save;
+mod highscores;
+mod item;
+mod crafting;
+mod camera;
+mod lighting;
+mod shader_params;
+mod difficulty;
+mod rng;
+mod player_input;
+mod replay;
+mod ghost;
+#[cfg(test)]
+mod headless;
 
 use bullet::BulletPlugin;
 use button::ButtonPlugin;
@@ -48,21 +67,39 @@ use enemy::EnemyPlugin;
 use fonts::FontPlugin;
 use main_menu::MainMenuPlugin;
 use music::MusicPlugin;
-use player::PlayerPlugin;
+use player::{PlayerPlugin, PlayerPostProcessingPlugin};
 use debug::DebugPlugin;
 use post_processing::PostProcessingPlugin;
 use settings::SettingsPlugin;
 use tilemap::TileMapPlugin;
 use game_over::GameOverPlugin;
 use audio::AudioLoadPlugin;
+use audio_player::AudioPlayerPlugin;
 use win::WinPlugin;
 use stats::StatsPlugin;
+use pause::PausePlugin;
+use checkpoint::CheckpointPlugin;
+use save::SavePlugin;
+use highscores::HighScoresPlugin;
+use item::ItemPlugin;
+use crafting::CraftingPlugin;
+use pickup::PickupPlugin;
+use camera::CameraPlugin;
+use lighting::LightingPlugin;
+use shader_params::ShaderParamsPlugin;
+use difficulty::DifficultyPlugin;
+use rng::RngPlugin;
+use player_input::PlayerInputPlugin;
+use replay::ReplayPlugin;
+use ghost::GhostPlugin;
+use storage::Storage;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub enum GameState {
     MainMenu,
     Settings,
     Game,
+    Paused,
     GameOver,
     Win,
     Stats,
@@ -95,6 +132,19 @@ fn set_window_icon(
 #[cfg(target_arch="wasm32")]
 fn set_window_icon(){}
 
+fn toggle_fullscreen(mut windows: ResMut<Windows>, keyboard: Res<Input<KeyCode>>) {
+    if !keyboard.just_pressed(KeyCode::F11) {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_mode(match window.mode() {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        });
+    }
+}
+
 fn main() {
     App::new()
         // States
@@ -105,12 +155,19 @@ fn main() {
             title: "Crackhead Adventures".to_string(),
             width: WIDTH,
             height: HEIGHT,
-            resizable: false,
+            resizable: true,
+            ..Default::default()
+        })
+        // Reloads the shaders when they change on disk
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: cfg!(debug_assertions),
             ..Default::default()
         })
+        .init_resource::<Storage>()
 
         // Setting window icon
         .add_startup_system(set_window_icon)
+        .add_system(toggle_fullscreen)
 
         // Plugins
         .add_plugins(DefaultPlugins)
@@ -120,10 +177,12 @@ fn main() {
         .add_plugin(MusicPlugin)
         .add_plugin(PostProcessingPlugin)
         .add_plugin(AudioLoadPlugin)
+        .add_plugin(AudioPlayerPlugin)
         .add_plugin(FontPlugin)
         .add_plugin(ButtonPlugin)
         .add_plugin(TileMapPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(PlayerPostProcessingPlugin)
         .add_plugin(EnemyPlugin)
         .add_plugin(BulletPlugin)
         .add_plugin(MainMenuPlugin)
@@ -133,6 +192,21 @@ fn main() {
         .add_plugin(DebugPlugin)
         .add_plugin(StatsPlugin)
         .add_plugin(SettingsPlugin)
+        .add_plugin(PausePlugin)
+        .add_plugin(CheckpointPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(HighScoresPlugin)
+        .add_plugin(ItemPlugin)
+        .add_plugin(CraftingPlugin)
+        .add_plugin(PickupPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(LightingPlugin)
+        .add_plugin(ShaderParamsPlugin)
+        .add_plugin(DifficultyPlugin)
+        .add_plugin(RngPlugin)
+        .add_plugin(PlayerInputPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(GhostPlugin)
         .add_plugin(DebugLinesPlugin::default())
 
         .run();
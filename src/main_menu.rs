@@ -1,13 +1,22 @@
 use bevy::{app::AppExit, prelude::*};
 
-use crate::{button::ColoredButton, fonts::{PaintFont, RobotoFont}, GameState};
+use crate::{button::ColoredButton, fonts::{PaintFont, RobotoFont}, save::{PendingLoad, SaveGame}, settings::Settings, storage::Storage, GameState};
 
 #[derive(Component)]
 struct MainMenuUi;
 
+#[derive(Component)]
+struct ContinueButton;
+
 #[derive(Component)]
 struct PlayButton;
 
+#[derive(Component)]
+struct DifficultyButton;
+
+#[derive(Component)]
+struct DifficultyName;
+
 #[derive(Component)]
 struct SettingsButton;
 
@@ -22,7 +31,9 @@ impl Plugin for MainMenuPlugin {
 		app.add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(load_ui))
 			.add_system_set(
 				SystemSet::on_update(GameState::MainMenu)
+					.with_system(continue_button)
 					.with_system(play_button)
+					.with_system(difficulty_button)
 					.with_system(exit_button)
 					.with_system(settings_button),
 			)
@@ -30,7 +41,18 @@ impl Plugin for MainMenuPlugin {
 	}
 }
 
-fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<RobotoFont>) {
+fn load_ui(
+	mut commands: Commands,
+	paint_font: Res<PaintFont>,
+	roboto_font: Res<RobotoFont>,
+	settings: Res<Settings>,
+	storage: Res<Storage>,
+) {
+	let can_continue = SaveGame::exists(&storage);
+
+	// Room for one more button
+	let continue_height = if can_continue { 75.0 } else { 0.0 };
+
 	commands
 		.spawn_bundle(NodeBundle {
 			style: Style {
@@ -67,9 +89,9 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 				.spawn_bundle(NodeBundle {
 					style: Style {
 						#[cfg(not(target_arch="wasm32"))]
-						size: Size::new(Val::Percent(50.0), Val::Px(200.0)),
+						size: Size::new(Val::Percent(50.0), Val::Px(275.0 + continue_height)),
 						#[cfg(target_arch="wasm32")]
-						size: Size::new(Val::Percent(50.0), Val::Px(135.0)),
+						size: Size::new(Val::Percent(50.0), Val::Px(210.0 + continue_height)),
 						justify_content: JustifyContent::SpaceBetween,
 						flex_direction: FlexDirection::ColumnReverse,
 						align_items: AlignItems::Center,
@@ -86,6 +108,33 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 				})
 				.insert(Name::new("ButtonsContainer"))
 				.with_children(|parent| {
+					if can_continue {
+						parent
+							.spawn_bundle(ButtonBundle {
+								style: Style {
+									size: Size::new(Val::Px(300.0), Val::Px(50.0)),
+									justify_content: JustifyContent::Center,
+									align_items: AlignItems::Center,
+									..Default::default()
+								},
+								color: Color::RED.into(),
+								..Default::default()
+							})
+							.insert(Name::new("ContinueButton"))
+							.insert(ColoredButton::default())
+							.insert(ContinueButton)
+							.with_children(|parent| {
+								parent.spawn_bundle(TextBundle::from_section(
+									"Continue",
+									TextStyle {
+										font: roboto_font.0.clone(),
+										font_size: 32.0,
+										color: Color::BLACK,
+									},
+								));
+							});
+					}
+
 					parent
 						.spawn_bundle(ButtonBundle {
 							style: Style {
@@ -111,6 +160,33 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 							));
 						});
 
+					parent
+						.spawn_bundle(ButtonBundle {
+							style: Style {
+								size: Size::new(Val::Px(300.0), Val::Px(50.0)),
+								justify_content: JustifyContent::Center,
+								align_items: AlignItems::Center,
+								..Default::default()
+							},
+							color: Color::RED.into(),
+							..Default::default()
+						})
+						.insert(Name::new("DifficultyButton"))
+						.insert(ColoredButton::default())
+						.insert(DifficultyButton)
+						.with_children(|parent| {
+							parent
+								.spawn_bundle(TextBundle::from_section(
+									format!("Difficulty: {}", settings.difficulty.name()),
+									TextStyle {
+										font: roboto_font.0.clone(),
+										font_size: 32.0,
+										color: Color::BLACK,
+									},
+								))
+								.insert(DifficultyName);
+						});
+
 					parent
 						.spawn_bundle(ButtonBundle {
 							style: Style {
@@ -218,6 +294,21 @@ fn drop_ui(mut commands: Commands, ui: Query<Entity, With<MainMenuUi>>) {
 	commands.entity(ui).despawn_recursive();
 }
 
+fn continue_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<ContinueButton>)>,
+	mut state: ResMut<State<GameState>>,
+	mut pending_load: ResMut<PendingLoad>,
+	storage: Res<Storage>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			pending_load.0 = SaveGame::take(&storage);
+
+			if state.set(GameState::Game).is_err() {}
+		}
+	}
+}
+
 fn play_button(
 	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<PlayButton>)>,
 	mut state: ResMut<State<GameState>>,
@@ -230,6 +321,22 @@ fn play_button(
 	}
 }
 
+fn difficulty_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<DifficultyButton>)>,
+	mut name_query: Query<&mut Text, With<DifficultyName>>,
+	mut settings: ResMut<Settings>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			settings.difficulty = settings.difficulty.next();
+
+			for mut name in &mut name_query {
+				name.sections[0].value = format!("Difficulty: {}", settings.difficulty.name());
+			}
+		}
+	}
+}
+
 fn settings_button(
 	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<SettingsButton>)>,
 	mut state: ResMut<State<GameState>>,
@@ -0,0 +1,276 @@
+use bevy::prelude::*;
+use bevy_kira_audio::{Audio, AudioControl, DynamicAudioChannels};
+use bevy_rapier2d::prelude::RapierConfiguration;
+
+use crate::{GameState, audio_player::world_channels, fonts::{PaintFont, RobotoFont}, button::ColoredButton, time::TimeCounter, save::SaveRunEvent, replay::Replays};
+
+#[derive(Component)]
+struct PauseUi;
+
+#[derive(Component)]
+struct ResumeButton;
+
+#[derive(Component)]
+struct SettingsButton;
+
+#[derive(Component)]
+struct RestartButton;
+
+#[derive(Component)]
+struct SaveAndQuitButton;
+
+#[derive(Component)]
+struct MainMenuButton;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(pause_on_escape)
+			)
+			.add_system_set(
+				SystemSet::on_pause(GameState::Game)
+					.with_system(freeze_game)
+			)
+			.add_system_set(
+				SystemSet::on_enter(GameState::Paused)
+					.with_system(load_ui)
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::Paused)
+					.with_system(resume_on_escape)
+					.with_system(resume_button)
+					.with_system(settings_button)
+					.with_system(restart_button)
+					.with_system(save_and_quit_button.label("save_and_quit_button"))
+					.with_system(main_menu_button)
+			)
+			.add_system_set(
+				// Not on resume, restarting and quitting replace the state and never resume the game
+				SystemSet::on_exit(GameState::Paused)
+					.with_system(drop_ui)
+					.with_system(unfreeze_game)
+			);
+	}
+}
+
+fn pause_on_escape(keyboard: Res<Input<KeyCode>>, replays: Res<Replays>, mut state: ResMut<State<GameState>>) {
+	// Escape stops a replay instead, it has to play every frame to stay in sync
+	#[allow(clippy::collapsible_if)]
+	if keyboard.just_pressed(KeyCode::Escape) && !replays.is_playing() {
+		if state.push(GameState::Paused).is_err() {}
+	}
+}
+
+fn resume_on_escape(mut keyboard: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+	if keyboard.just_pressed(KeyCode::Escape) {
+		// Otherwise the game would pause again right away
+		keyboard.clear_just_pressed(KeyCode::Escape);
+
+		if state.pop().is_err() {}
+	}
+}
+
+fn freeze_game(
+	mut time: ResMut<TimeCounter>,
+	mut rapier_configuration: ResMut<RapierConfiguration>,
+	mut windows: ResMut<Windows>,
+	audio: Res<Audio>,
+	channels: Res<DynamicAudioChannels>,
+) {
+	time.pause();
+	rapier_configuration.physics_pipeline_active = false;
+	audio.pause();
+
+	for channel in world_channels(&channels) {
+		channel.pause();
+	}
+
+	// The crosshair only makes sense in game, so give the cursor back
+	if let Some(window) = windows.get_primary_mut() {
+		window.set_cursor_visibility(true);
+	}
+}
+
+fn unfreeze_game(
+	mut time: ResMut<TimeCounter>,
+	mut rapier_configuration: ResMut<RapierConfiguration>,
+	mut windows: ResMut<Windows>,
+	audio: Res<Audio>,
+	channels: Res<DynamicAudioChannels>,
+) {
+	time.resume();
+	rapier_configuration.physics_pipeline_active = true;
+	audio.resume();
+
+	for channel in world_channels(&channels) {
+		channel.resume();
+	}
+
+	if let Some(window) = windows.get_primary_mut() {
+		window.set_cursor_visibility(false);
+	}
+}
+
+fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<RobotoFont>) {
+	commands
+		.spawn_bundle(NodeBundle {
+			style: Style {
+				size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+				justify_content: JustifyContent::Center,
+				align_items: AlignItems::Center,
+				flex_direction: FlexDirection::ColumnReverse,
+				..Default::default()
+			},
+			color: UiColor(Color::rgba(0.0, 0.0, 0.0, 0.7)),
+			..Default::default()
+		})
+		.insert(PauseUi)
+		.insert(Name::new("PauseUi"))
+		.with_children(|parent| {
+			parent
+				.spawn_bundle(
+					TextBundle::from_section(
+						"Paused",
+						TextStyle {
+							font: paint_font.0.clone(),
+							font_size: 152.0,
+							color: Color::WHITE,
+						},
+					)
+					.with_style(Style {
+						margin: UiRect::all(Val::Px(5.0)),
+						..default()
+					}),
+				)
+				.insert(Name::new("Title"));
+
+			parent
+				.spawn_bundle(NodeBundle {
+					style: Style {
+						size: Size::new(Val::Percent(50.0), Val::Px(350.0)),
+						justify_content: JustifyContent::SpaceBetween,
+						flex_direction: FlexDirection::ColumnReverse,
+						align_items: AlignItems::Center,
+						margin: UiRect::new(
+							Val::Px(0.0),
+							Val::Px(0.0),
+							Val::Px(100.0),
+							Val::Px(0.0),
+						),
+						..Default::default()
+					},
+					color: Color::NONE.into(),
+					..Default::default()
+				})
+				.insert(Name::new("ButtonsContainer"))
+				.with_children(|parent| {
+					spawn_button(parent, &roboto_font.0, "Resume", "ResumeButton", ResumeButton);
+					spawn_button(parent, &roboto_font.0, "Settings", "SettingsButton", SettingsButton);
+					spawn_button(parent, &roboto_font.0, "Restart", "RestartButton", RestartButton);
+					spawn_button(parent, &roboto_font.0, "Save & Quit", "SaveAndQuitButton", SaveAndQuitButton);
+					spawn_button(parent, &roboto_font.0, "Main Menu", "MainMenuButton", MainMenuButton);
+				});
+		});
+}
+
+fn spawn_button(
+	parent: &mut ChildBuilder,
+	font: &Handle<Font>,
+	label: &str,
+	name: &'static str,
+	marker: impl Component,
+) {
+	parent
+		.spawn_bundle(ButtonBundle {
+			style: Style {
+				size: Size::new(Val::Px(300.0), Val::Px(50.0)),
+				justify_content: JustifyContent::Center,
+				align_items: AlignItems::Center,
+				..Default::default()
+			},
+			button: Button,
+			color: Color::RED.into(),
+			..Default::default()
+		})
+		.insert(Name::new(name))
+		.insert(ColoredButton::default())
+		.insert(marker)
+		.with_children(|parent| {
+			parent.spawn_bundle(TextBundle::from_section(
+				label,
+				TextStyle {
+					font: font.clone(),
+					font_size: 32.0,
+					color: Color::BLACK,
+				},
+			));
+		});
+}
+
+fn drop_ui(mut commands: Commands, ui: Query<Entity, With<PauseUi>>) {
+	let ui = ui.single();
+	commands.entity(ui).despawn_recursive();
+}
+
+fn resume_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<ResumeButton>)>,
+	mut state: ResMut<State<GameState>>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			state.pop().expect("Failed to change state!");
+		}
+	}
+}
+
+fn settings_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<SettingsButton>)>,
+	mut state: ResMut<State<GameState>>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			state.push(GameState::Settings).expect("Failed to change state!");
+		}
+	}
+}
+
+fn restart_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<RestartButton>)>,
+	mut state: ResMut<State<GameState>>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			// Replacing unwinds the whole stack, so the level gets dropped and loaded again
+			state.replace(GameState::Game).expect("Failed to change state!");
+		}
+	}
+}
+
+fn save_and_quit_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<SaveAndQuitButton>)>,
+	mut state: ResMut<State<GameState>>,
+	mut save_events: EventWriter<SaveRunEvent>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			// The save gets written before the level is dropped at the end of the frame
+			save_events.send(SaveRunEvent);
+			state.replace(GameState::MainMenu).expect("Failed to change state!");
+		}
+	}
+}
+
+fn main_menu_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainMenuButton>)>,
+	mut state: ResMut<State<GameState>>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			state.replace(GameState::MainMenu).expect("Failed to change state!");
+		}
+	}
+}
@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use tiled::{Properties, PropertyValue};
+
+use crate::audio_player::{PlaySound, SoundId};
+use crate::checkpoint::Checkpoints;
+use crate::fonts::PaintFont;
+use crate::item::{ItemRegistry, COCAINE};
+use crate::player::{EffectRegistry, Effects, Player};
+use crate::stats::Stats;
+use crate::tilemap::{Tile, TilePosition};
+use crate::time::TimeCounter;
+use crate::unit::{Health, Inventory};
+use crate::{GameState, TILE_SIZE};
+
+/// Pickups closer than this fly towards the player while they're high
+const MAGNET_RADIUS: f32 = TILE_SIZE * 4.0;
+const MAGNET_SPEED: f32 = TILE_SIZE * 8.0;
+
+const FLOATING_TEXT_DURATION: f32 = 1.0;
+const FLOATING_TEXT_SPEED: f32 = TILE_SIZE;
+
+#[derive(Clone, Debug)]
+pub enum PickupReward {
+	Item { id: String, quantity: u32 },
+	Health(f32),
+	Armor(f32),
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Pickup {
+	pub reward: PickupReward,
+	pub radius: f32,
+}
+
+impl Default for Pickup {
+	fn default() -> Self {
+		Self::item(COCAINE, 1)
+	}
+}
+
+impl Pickup {
+	pub fn item(id: &str, quantity: u32) -> Self {
+		Self {
+			reward: PickupReward::Item {
+				id: id.to_string(),
+				quantity,
+			},
+			radius: TILE_SIZE / 4.0,
+		}
+	}
+
+	pub fn health(amount: f32) -> Self {
+		Self {
+			reward: PickupReward::Health(amount),
+			radius: TILE_SIZE / 4.0,
+		}
+	}
+
+	pub fn armor(amount: f32) -> Self {
+		Self {
+			reward: PickupReward::Armor(amount),
+			radius: TILE_SIZE / 4.0,
+		}
+	}
+
+	/// Reads a pickup from the properties of a tile in the tileset,
+	/// either `pickup_item` with an optional `pickup_quantity`, `pickup_health` or `pickup_armor`.
+	pub fn from_properties(properties: &Properties) -> Option<Self> {
+		if let Some(PropertyValue::StringValue(id)) = properties.get("pickup_item") {
+			let quantity = match properties.get("pickup_quantity") {
+				Some(PropertyValue::IntValue(quantity)) => (*quantity).max(1) as u32,
+				_ => 1,
+			};
+
+			return Some(Self::item(id, quantity));
+		}
+
+		match properties.get("pickup_health") {
+			Some(PropertyValue::FloatValue(amount)) => return Some(Self::health(*amount)),
+			Some(PropertyValue::IntValue(amount)) => return Some(Self::health(*amount as f32)),
+			_ => (),
+		}
+
+		match properties.get("pickup_armor") {
+			Some(PropertyValue::FloatValue(amount)) => Some(Self::armor(*amount)),
+			Some(PropertyValue::IntValue(amount)) => Some(Self::armor(*amount as f32)),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Bundle)]
+pub struct PickupBundle {
+	#[bundle]
+	sprite_bundle: SpriteBundle,
+	pickup: Pickup,
+	collider: Collider,
+	sensor: Sensor,
+	// Neither the player nor the pickups have rigid bodies, so static-static intersections have to be enabled
+	collision_types: ActiveCollisionTypes,
+	name: Name,
+}
+
+impl Default for PickupBundle {
+	fn default() -> Self {
+		let pickup = Pickup::default();
+
+		Self {
+			sprite_bundle: SpriteBundle::default(),
+			collider: Collider::ball(pickup.radius),
+			pickup,
+			sensor: Sensor,
+			collision_types: ActiveCollisionTypes::default() | ActiveCollisionTypes::STATIC_STATIC,
+			name: Name::new("Pickup"),
+		}
+	}
+}
+
+impl PickupBundle {
+	pub fn with_pickup(mut self, pickup: Pickup) -> Self {
+		self.collider = Collider::ball(pickup.radius);
+		self.pickup = pickup;
+		self
+	}
+}
+
+impl Tile for PickupBundle {
+	fn spawn(position: Vec2, texture: Handle<Image>, flip_x: bool, flip_y: bool) -> Self {
+		Self {
+			sprite_bundle: SpriteBundle {
+				transform: Transform::from_translation(position.extend(25.0)),
+				sprite: Sprite {
+					flip_x,
+					flip_y,
+					..Default::default()
+				},
+				texture,
+				..Default::default()
+			},
+			..Default::default()
+		}
+	}
+}
+
+#[derive(Component)]
+struct FloatingText {
+	timer: Timer,
+}
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_system_set(
+			SystemSet::on_update(GameState::Game)
+				.with_system(attract_pickups.label("attract_pickups"))
+				.with_system(collect_pickups.after("attract_pickups"))
+				.with_system(update_floating_texts),
+		)
+		.add_system_set(SystemSet::on_exit(GameState::Game).with_system(drop_floating_texts));
+	}
+}
+
+fn attract_pickups(
+	player_query: Query<(&Transform, &Effects), With<Player>>,
+	mut pickup_query: Query<&mut Transform, (With<Pickup>, Without<Player>)>,
+	registry: Res<EffectRegistry>,
+	time: Res<TimeCounter>,
+) {
+	let (player_transform, effects) = player_query.single();
+
+	if !effects.is_high(&registry) {
+		return;
+	}
+
+	let player_position = player_transform.translation.truncate();
+
+	for mut transform in pickup_query.iter_mut() {
+		let offset = player_position - transform.translation.truncate();
+		let distance = offset.length();
+
+		if distance > MAGNET_RADIUS || distance == 0.0 {
+			continue;
+		}
+
+		let step = (MAGNET_SPEED * time.delta_seconds()).min(distance);
+		transform.translation += (offset / distance * step).extend(0.0);
+	}
+}
+
+fn collect_pickups(
+	mut commands: Commands,
+	mut player_query: Query<(Entity, &mut Inventory, &mut Health), With<Player>>,
+	pickup_query: Query<(Entity, &Pickup, &Transform, &TilePosition)>,
+	rapier_context: Res<RapierContext>,
+	items: Res<ItemRegistry>,
+	mut stats: ResMut<Stats>,
+	mut checkpoints: ResMut<Checkpoints>,
+	mut sounds: EventWriter<PlaySound>,
+	font: Res<PaintFont>,
+) {
+	let (player, mut inventory, mut health) = player_query.single_mut();
+
+	for (pickup_entity, pickup, transform, tile) in pickup_query.iter() {
+		if rapier_context.intersection_pair(player, pickup_entity) != Some(true) {
+			continue;
+		}
+
+		let text = match &pickup.reward {
+			PickupReward::Item { id, quantity } => {
+				let item = match items.get(id) {
+					Some(item) => item,
+					None => continue,
+				};
+
+				let added = inventory.add(id, *quantity, item.max_stack);
+
+				// Leave it lying there if there's no room for it
+				if added == 0 {
+					continue;
+				}
+
+				*stats.items_collected.entry(id.clone()).or_default() += added as u16;
+
+				format!("+{} {}", added, item.name)
+			}
+			PickupReward::Health(amount) => {
+				if health.get_health() >= health.get_max_health() {
+					continue;
+				}
+
+				health.heal(*amount);
+
+				format!("+{} Health", amount)
+			}
+			PickupReward::Armor(amount) => {
+				if health.get_armor() >= health.get_max_armor() {
+					continue;
+				}
+
+				health.add_armor(*amount);
+
+				format!("+{} Armor", amount)
+			}
+		};
+
+		commands
+			.spawn_bundle(Text2dBundle {
+				text: Text::from_section(
+					text,
+					TextStyle {
+						font: font.0.clone(),
+						font_size: 28.0,
+						color: Color::PINK,
+					},
+				)
+				.with_alignment(TextAlignment::CENTER),
+				transform: Transform::from_translation(transform.translation.truncate().extend(90.0)),
+				..Default::default()
+			})
+			.insert(FloatingText {
+				timer: Timer::from_seconds(FLOATING_TEXT_DURATION, false),
+			})
+			.insert(Name::new("FloatingText"));
+
+		sounds.send(PlaySound::new(SoundId::Pickup).at(transform.translation.truncate()));
+
+		checkpoints.pickup_collected(tile.0);
+		commands.entity(pickup_entity).despawn_recursive();
+	}
+}
+
+/// Makes the texts float upwards and fade out.
+fn update_floating_texts(
+	mut commands: Commands,
+	mut text_query: Query<(Entity, &mut Transform, &mut Text, &mut FloatingText)>,
+	time: Res<TimeCounter>,
+) {
+	for (entity, mut transform, mut text, mut floating_text) in text_query.iter_mut() {
+		floating_text.timer.tick(time.delta());
+
+		if floating_text.timer.finished() {
+			commands.entity(entity).despawn_recursive();
+			continue;
+		}
+
+		transform.translation.y += FLOATING_TEXT_SPEED * time.delta_seconds();
+
+		for section in &mut text.sections {
+			section.style.color.set_a(1.0 - floating_text.timer.percent());
+		}
+	}
+}
+
+fn drop_floating_texts(mut commands: Commands, text_query: Query<Entity, With<FloatingText>>) {
+	for entity in text_query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
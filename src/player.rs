@@ -4,87 +4,103 @@ use bevy::prelude::*;
 
 use bevy_rapier2d::prelude::*;
 
-use bevy_kira_audio::prelude::*;
+use rand::Rng;
 
-use rand::prelude::*;
-
-use rand::seq::SliceRandom;
-
-use crate::audio::{CraftingSound, FootstepSounds, ShotgunSound, SnortingSounds};
-use crate::audio_player::{
-	AudioPlayer, PLAYER_FOOTSTEP_VOLUME, PLAYER_SHOT_VOLUME, PLAYER_SNORTING_VOLUME, PLAYER_CRAFTING_VOLUME,
-};
+use crate::audio_player::{PlaySound, SoundId, SoundPriority};
 use crate::bullet::{Bullet, BulletBundle, BulletTexture, ShotEvent};
-use crate::cocaine::Cocaine;
+use crate::camera::{cursor_world_position, Viewport};
+use crate::difficulty::DifficultyScaling;
 use crate::enemy::Enemy;
-use crate::post_processing::{
-	update_post_processing_effects, CameraRenderImage, DefaultMaterial, MainCamera,
-	PostProcessingLayer, ScreenRes,
-};
-use crate::settings::Settings;
+use crate::item::{ItemRegistry, UseAction, COCAINE, SHELLS};
+use crate::player_input::PlayerInput;
+use crate::post_processing::MainCamera;
+use crate::rng::GameRng;
 use crate::stats::Stats;
 use crate::tilemap::{Tile, Tilemap};
-use crate::time::TimeCounter;
-use crate::unit::{Health, Inventory, Movement, ShootEvent, Shooting};
+use crate::time::{TimeCounter, TimeLayer};
+use crate::unit::{BaseStats, DamageEvent, Health, Inventory, Movement, ShootEvent, Shooting};
 use crate::win::Win;
-use crate::HEIGHT;
-use crate::WIDTH;
 use crate::{GameState, TILE_SIZE};
 
 mod effect;
 mod post_processing;
 mod ui;
 
-use ui::{drop_ui, ui_setup, update_ui};
+use ui::{drop_ui, ui_setup, update_armor_bar, update_inventory_slots, update_ui};
 
-use self::effect::{BigPowerup, EffectData, SmallPowerup};
-use self::post_processing::{
-	clean_post_processing, BigPowerupMaterial, PlayerPostProcessingPlugin, SmallPowerupMaterial,
-};
+pub use self::effect::{ActiveEffect, Effects};
+pub use self::ui::SplitTimeUI;
+pub use self::effect::EffectRegistry;
+use self::effect::update_effects;
+pub use self::post_processing::PlayerPostProcessingPlugin;
 
 pub const WEAPON_COOLDOWN: f32 = 0.5;
-pub const SMALL_POWERUP_DURATION: f32 = 5.0;
-pub const BIG_POWERUP_DURATION: f32 = 5.0 / 3.0;
+
+/// Below this part of their max health the edges of the screen go red and the heartbeat starts
+const LOW_HEALTH: f32 = 0.4;
+
+const PLAYER_MAX_ARMOR: f32 = 100.0;
+
+/// Seconds between heartbeats, right below `LOW_HEALTH` and right before dying
+const SLOWEST_HEARTBEAT: f32 = 1.2;
+const FASTEST_HEARTBEAT: f32 = 0.5;
 
 #[derive(Component)]
 pub struct Player;
 
+/// How forgiving the health of the player is, set by the difficulty.
+#[derive(Clone)]
+pub struct HealthTuning {
+	/// Seconds without getting hurt before the health starts coming back, None turns regeneration off
+	pub regeneration_delay: Option<f32>,
+	pub regeneration_per_second: f32,
+	/// Regeneration stops at this part of the max health, so medkits are still worth picking up
+	pub regeneration_limit: f32,
+}
+
+/// Keeps track of when the player last got hurt.
+#[derive(Component, Default)]
+pub struct Regeneration {
+	since_hurt: f32,
+	last_total: f32,
+}
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
 	fn build(&self, app: &mut App) {
-		app.add_plugin(PlayerPostProcessingPlugin)
-			.register_type::<Movement>()
-			.insert_resource(ActiveMaterial(None))
+		app.register_type::<Movement>()
+			.insert_resource(EffectRegistry::load())
 			.add_event::<ShootEvent>()
+			.add_event::<DamageEvent>()
 			.add_system_set(
 				SystemSet::on_enter(GameState::Game)
 					.with_system(ui_setup)
-					.with_system(setup_footstep_timer),
+					.with_system(setup_footstep_timer)
+					.with_system(setup_heartbeat_timer),
 			)
 			.add_system_set(
 				SystemSet::on_exit(GameState::Game)
 					.with_system(drop_ui)
-					.with_system(drop_footstep_timer),
+					.with_system(drop_footstep_timer)
+					.with_system(drop_heartbeat_timer)
+					.with_system(reset_timescale),
 			)
 			.add_system_set(
 				SystemSet::on_update(GameState::Game)
-					.with_system(player_movement.label("player_movement"))
-					.with_system(
-						camera_follow
-							.label("camera_follow_player")
-							.after("player_movement"),
-					)
-					.with_system(player_aim.label("player_aim").after("player_movement"))
+					.with_system(update_effects.label("update_effects"))
+					.with_system(player_movement.label("player_movement").after("update_effects"))
+					.with_system(player_aim.label("player_aim").after("camera_follow_player"))
 					.with_system(player_shoot.after("player_aim"))
 					.with_system(damage_yourself)
 					.with_system(get_shot)
+					.with_system(heartbeat)
+					.with_system(regenerate_health.after("update_effects"))
 					.with_system(win_condition)
 					.with_system(update_ui)
-					.with_system(pick_up_cocaine)
-					.with_system(craft_magic_dust)
-					.with_system(use_powerup)
-					.with_system(update_powerup_material),
+					.with_system(update_armor_bar)
+					.with_system(update_inventory_slots)
+					.with_system(use_item.label("use_item")),
 			);
 	}
 }
@@ -96,11 +112,13 @@ pub struct PlayerBundle {
 	name: Name,
 	player: Player,
 	movement: Movement,
+	base_stats: BaseStats,
 	health: Health,
+	regeneration: Regeneration,
 	shooting: Shooting,
 	rapier_collider: Collider,
 	inventory: Inventory,
-	effect: EffectData,
+	effects: Effects,
 }
 
 impl Default for PlayerBundle {
@@ -117,15 +135,17 @@ impl Default for PlayerBundle {
 			name: Name::new("Player"),
 			player: Player,
 			movement: Movement { speed: 10.0 },
-			health: Health::new(100.0),
+			base_stats: BaseStats {
+				speed: 10.0,
+				weapon_cooldown: WEAPON_COOLDOWN,
+			},
+			health: Health::new(100.0).with_max_armor(PLAYER_MAX_ARMOR),
+			regeneration: Regeneration::default(),
 			shooting: Shooting {
 				cooldown: Timer::new(Duration::from_secs_f32(WEAPON_COOLDOWN), false),
 			},
-			inventory: Inventory::new(),
-			effect: EffectData {
-				effect: None,
-				duration: Timer::from_seconds(0.0, false),
-			},
+			inventory: Inventory::with_items([(COCAINE, 1), (SHELLS, 24)]),
+			effects: Effects::default(),
 			rapier_collider: Collider::ball(TILE_SIZE / 2.0),
 		}
 	}
@@ -161,16 +181,82 @@ fn drop_footstep_timer(mut commands: Commands) {
 	commands.remove_resource::<FootstepTimer>();
 }
 
+/// Slowly heals the player once they haven't been hurt for a while.
+fn regenerate_health(
+	mut player_query: Query<(&mut Health, &mut Regeneration), With<Player>>,
+	difficulty: Res<DifficultyScaling>,
+	time: Res<TimeCounter>,
+) {
+	let (mut health, mut regeneration) = player_query.single_mut();
+	let tuning = &difficulty.health;
+
+	if health.total() < regeneration.last_total {
+		regeneration.since_hurt = 0.0;
+	} else {
+		regeneration.since_hurt += time.delta_seconds();
+	}
+
+	let limit = health.get_max_health() * tuning.regeneration_limit;
+
+	if let Some(delay) = tuning.regeneration_delay {
+		if regeneration.since_hurt >= delay && health.get_health() < limit {
+			let amount = (tuning.regeneration_per_second * time.delta_seconds()).min(limit - health.get_health());
+
+			health.heal(amount);
+		}
+	}
+
+	regeneration.last_total = health.total();
+}
+
+struct HeartbeatTimer(Timer);
+
+fn setup_heartbeat_timer(mut commands: Commands) {
+	commands.insert_resource(HeartbeatTimer(Timer::from_seconds(SLOWEST_HEARTBEAT, true)));
+}
+
+fn drop_heartbeat_timer(mut commands: Commands) {
+	commands.remove_resource::<HeartbeatTimer>();
+}
+
+/// Plays a heartbeat while the player is low on health, faster and louder the closer they are to dying.
+fn heartbeat(
+	player_query: Query<&Health, With<Player>>,
+	mut heartbeat_timer: ResMut<HeartbeatTimer>,
+	time: Res<TimeCounter>,
+	mut sounds: EventWriter<PlaySound>,
+) {
+	let health = player_query.single();
+	let ratio = (health.get_health() / health.get_max_health()).max(0.0);
+
+	if ratio >= LOW_HEALTH {
+		heartbeat_timer.0.reset();
+		return;
+	}
+
+	let danger = 1.0 - ratio / LOW_HEALTH;
+	let interval = SLOWEST_HEARTBEAT - (SLOWEST_HEARTBEAT - FASTEST_HEARTBEAT) * danger;
+
+	heartbeat_timer.0.set_duration(Duration::from_secs_f32(interval));
+	heartbeat_timer.0.tick(time.delta());
+
+	if heartbeat_timer.0.just_finished() {
+		sounds.send(
+			PlaySound::new(SoundId::Heartbeat)
+				.with_priority(SoundPriority::High)
+				.with_volume(0.5 + 0.5 * danger as f64),
+		);
+	}
+}
+
 fn player_movement(
 	mut player_query: Query<(Entity, &Movement, &mut Transform, &Collider), With<Player>>,
 	enemy_query: Query<Entity, (With<Enemy>, Without<Player>)>,
-	keyboard: Res<Input<KeyCode>>,
+	input: Res<PlayerInput>,
 	time: Res<TimeCounter>,
-	settings: Res<Settings>,
-	audio: Res<Audio>,
 	rapier_context: Res<RapierContext>,
-	footstep_sounds: Res<FootstepSounds>,
 	mut footstep_timer: ResMut<FootstepTimer>,
+	mut sounds: EventWriter<PlaySound>,
 ) {
 	let (player_entity, movement, mut transform, rapier_collider) = player_query
 		.iter_mut()
@@ -181,19 +267,19 @@ fn player_movement(
 
 	let mut direction = Vec3::new(0.0, 0.0, 0.0);
 
-	if keyboard.pressed(KeyCode::W) {
+	if input.pressed('W') {
 		direction.y += 1.0;
 	}
 
-	if keyboard.pressed(KeyCode::S) {
+	if input.pressed('S') {
 		direction.y -= 1.0;
 	}
 
-	if keyboard.pressed(KeyCode::D) {
+	if input.pressed('D') {
 		direction.x += 1.0;
 	}
 
-	if keyboard.pressed(KeyCode::A) {
+	if input.pressed('A') {
 		direction.x -= 1.0;
 	}
 
@@ -207,6 +293,7 @@ fn player_movement(
 
 		let filter = QueryFilter::default()
 			.exclude_collider(player_entity)
+			.exclude_sensors()
 			.predicate(&predicate);
 
 		let x_movement = if let Some((_, hit)) = rapier_context.cast_shape(
@@ -245,41 +332,27 @@ fn player_movement(
 
 		footstep_timer.tick(time.delta());
 		if movement_vector != Vec2::ZERO && footstep_timer.finished() {
-			AudioPlayer::play_sfx(
-				audio.as_ref(),
-				footstep_sounds
-					.choose(&mut rand::thread_rng())
-					.expect("No footstep sounds found.")
-					.clone(),
-				PLAYER_FOOTSTEP_VOLUME,
-				settings.as_ref(),
+			sounds.send(
+				PlaySound::new(SoundId::Footstep)
+					.at(transform.translation.truncate())
+					.with_priority(SoundPriority::Low),
 			);
 			footstep_timer.reset();
 		}
 	}
 }
 
-fn camera_follow(
-	player_query: Query<&Transform, With<Player>>,
-	mut camera_query: Query<&mut Transform, (Without<Player>, With<MainCamera>)>,
-) {
-	let mut camera_transform = camera_query.single_mut();
-	let player_transform = player_query.single();
-
-	camera_transform.translation.x = player_transform.translation.x;
-	camera_transform.translation.y = player_transform.translation.y;
-}
-
 fn damage_yourself(
 	mut player_query: Query<&mut Health, With<Player>>,
 	keyboard: Res<Input<KeyCode>>,
 	mut state: ResMut<State<GameState>>,
+	mut rng: ResMut<GameRng>,
 ) {
 	let mut player_health = player_query.single_mut();
 
 	#[allow(clippy::collapsible_if)]
 	if cfg!(debug_assertions) && keyboard.just_pressed(KeyCode::Space) {
-		if player_health.take_damage(rand::thread_rng().gen::<f32>() * 10.0 + 10.0) {
+		if player_health.take_damage(rng.gameplay().gen::<f32>() * 10.0 + 10.0) {
 			state
 				.set(GameState::GameOver)
 				.expect("Failed to change states");
@@ -287,13 +360,19 @@ fn damage_yourself(
 	}
 }
 
-fn player_aim(mut player_query: Query<&mut Transform, With<Player>>, window: Res<Windows>) {
+fn player_aim(
+	mut player_query: Query<&mut Transform, With<Player>>,
+	camera_query: Query<(Option<&Camera>, &Transform), (With<MainCamera>, Without<Player>)>,
+	input: Res<PlayerInput>,
+	viewport: Res<Viewport>,
+) {
 	let mut player_transform = player_query.single_mut();
+	let (camera, camera_transform) = camera_query.single();
 
-	if let Some(target) = window.iter().next().unwrap().cursor_position() {
-		let window_size = Vec2::new(WIDTH as f32, HEIGHT as f32);
+	let target = cursor_world_position(input.as_ref(), camera, viewport.as_ref(), camera_transform);
 
-		let target = target - window_size / 2.0;
+	if let Some(target) = target {
+		let target = target - player_transform.translation.truncate();
 
 		let angle = Vec2::Y.angle_between(target);
 		player_transform.rotation = Quat::from_rotation_z(angle);
@@ -302,19 +381,18 @@ fn player_aim(mut player_query: Query<&mut Transform, With<Player>>, window: Res
 
 fn player_shoot(
 	mut commands: Commands,
-	mut player_query: Query<(Entity, &Transform, &mut Shooting), With<Player>>,
+	mut player_query: Query<(Entity, &Transform, &mut Shooting, &mut Inventory), With<Player>>,
 	world_query: Query<Entity, With<Tilemap>>,
 	mut event_shot: EventWriter<ShootEvent>,
-	buttons: Res<Input<MouseButton>>,
+	input: Res<PlayerInput>,
 	time: Res<TimeCounter>,
 	_rapier_context: Res<RapierContext>,
-	settings: Res<Settings>,
-	audio: Res<Audio>,
-	shot_sound: Res<ShotgunSound>,
+	mut sounds: EventWriter<PlaySound>,
 	bullet_texture: Res<BulletTexture>,
 	mut stats: ResMut<Stats>,
+	mut rng: ResMut<GameRng>,
 ) {
-	let (_player, player_transform, mut shooting) = player_query.single_mut();
+	let (_player, player_transform, mut shooting, mut inventory) = player_query.single_mut();
 	let world = world_query.single();
 
 	shooting.cooldown.tick(time.delta());
@@ -323,7 +401,8 @@ fn player_shoot(
 		return;
 	}
 
-	if buttons.just_pressed(MouseButton::Left) {
+	// Every shot uses up a shell, an empty gun doesn't fire until more shells are picked up
+	if input.shoot() && inventory.remove(SHELLS, 1) {
 		// Spawn the bullets
 		let mut bullets = Vec::new();
 
@@ -331,7 +410,7 @@ fn player_shoot(
 			let mut bullet_transform = player_transform
 				.with_translation(player_transform.translation + player_transform.up() * TILE_SIZE);
 
-			bullet_transform.rotate_z((i - 2) as f32 * (0.02 + random::<f32>() * 0.01));
+			bullet_transform.rotate_z((i - 2) as f32 * (0.02 + rng.gameplay().gen::<f32>() * 0.01));
 
 			bullets.push(
 				commands
@@ -341,7 +420,10 @@ fn player_shoot(
 							texture: bullet_texture.clone(),
 							..Default::default()
 						},
-						bullet: Bullet { speed: 2000.0 },
+						bullet: Bullet {
+							speed: 2000.0,
+							origin: player_transform.translation.truncate(),
+						},
 						..Default::default()
 					})
 					.id(),
@@ -350,11 +432,10 @@ fn player_shoot(
 
 		commands.entity(world).push_children(&bullets);
 
-		AudioPlayer::play_sfx(
-			audio.into_inner(),
-			shot_sound.clone(),
-			PLAYER_SHOT_VOLUME,
-			settings.into_inner(),
+		sounds.send(
+			PlaySound::new(SoundId::Shotgun)
+				.at(player_transform.translation.truncate())
+				.with_priority(SoundPriority::High),
 		);
 
 		stats.shot_fired += 1;
@@ -366,208 +447,67 @@ fn player_shoot(
 	}
 }
 
-// It actually doesn't make any sense but it's the fastest (and also hackiest) way to do this
-enum PowerupMaterial {
-	SmallPowerup(Handle<SmallPowerupMaterial>),
-	BigPowerup(Handle<BigPowerupMaterial>),
-}
-
-#[derive(Deref, DerefMut)]
-struct ActiveMaterial(Option<PowerupMaterial>);
-
-fn use_powerup(
-	mut commands: Commands,
-	mut player_query: Query<
-		(&mut Inventory, &mut Movement, &mut Health, &mut Shooting, &mut EffectData),
-		With<Player>,
-	>,
-	keyboard: Res<Input<KeyCode>>,
-	mut time: ResMut<TimeCounter>,
-	settings: Res<Settings>,
-	audio: Res<Audio>,
-	snorting_sounds: Res<SnortingSounds>,
-	post_processing_pass_layer: Res<PostProcessingLayer>,
-	screen: Res<ScreenRes>,
-	mut active_effect: ResMut<ActiveMaterial>,
-	source_image: Res<CameraRenderImage>,
-	mut default_materials: ResMut<Assets<DefaultMaterial>>,
-	mut small_powerup_materials: ResMut<Assets<SmallPowerupMaterial>>,
-	mut big_powerup_materials: ResMut<Assets<BigPowerupMaterial>>,
-	mut meshes: ResMut<Assets<Mesh>>,
+fn use_item(
+	mut player_query: Query<(&mut Inventory, &mut Health, &mut Effects), With<Player>>,
+	input: Res<PlayerInput>,
+	items: Res<ItemRegistry>,
+	registry: Res<EffectRegistry>,
+	mut sounds: EventWriter<PlaySound>,
 	mut stats: ResMut<Stats>,
 ) {
-	let (mut inventory, mut movement, mut health, mut shooting, mut effect_data) = player_query.single_mut();
-
-	effect_data.duration.tick(time.delta());
-
-	if effect_data.duration.just_finished() {
-		// Remove the effects
-		effect_data.finish(movement.as_mut(), health.as_mut(), shooting.as_mut(), time.as_mut());
-		clean_post_processing(
-			&mut commands,
-			&screen.0,
-			&mut meshes,
-			&post_processing_pass_layer,
-			&source_image,
-			&mut default_materials,
-		);
+	let (mut inventory, mut health, mut effects) = player_query.single_mut();
 
-		active_effect.0 = None;
-	}
+	let item = items.iter().find(|item| {
+		item.use_action.is_some()
+			&& item.hotkey.map_or(false, |hotkey| input.just_pressed(hotkey))
+			&& inventory.quantity(&item.id) > 0
+	});
+
+	let item = match item {
+		Some(item) => item,
+		None => return,
+	};
 
-	// Don't take more drugs if you're high already
-	if effect_data.effect.is_some() {
+	// Don't waste a medkit
+	if matches!(item.use_action, Some(UseAction::Heal(_))) && health.get_health() >= health.get_max_health() {
 		return;
 	}
 
-	// Small powerup is under E
-	if keyboard.just_pressed(KeyCode::E) && inventory.subtract_small_powerup(1) {
-		effect_data.apply(
-			Some(Box::new(SmallPowerup)),
-			movement.as_mut(),
-			health.as_mut(),
-			shooting.as_mut(),
-			time.as_mut(),
-			SMALL_POWERUP_DURATION,
-		);
-
-		let powerup =
-			small_powerup_materials.add(SmallPowerupMaterial::new(source_image.0.clone()));
-
-		// Add a post-processing effect
-		update_post_processing_effects(
-			&mut commands,
-			&screen,
-			powerup.clone(),
-			&mut meshes,
-			&post_processing_pass_layer,
-		);
-
-		active_effect.0 = Some(PowerupMaterial::SmallPowerup(powerup));
-
-		AudioPlayer::play_sfx(
-			audio.as_ref(),
-			snorting_sounds
-				.choose(&mut rand::thread_rng())
-				.expect("No snorting sounds!")
-				.clone(),
-			PLAYER_SNORTING_VOLUME,
-			settings.as_ref(),
-		);
-
-		stats.small_powerup_used += 1;
+	if !inventory.remove(&item.id, 1) {
+		return;
 	}
-	// Big powerup is under R
-	else if keyboard.just_pressed(KeyCode::R) && inventory.subtract_big_powerup(1) {
-		effect_data.apply(
-			Some(Box::new(BigPowerup)),
-			movement.as_mut(),
-			health.as_mut(),
-			shooting.as_mut(),
-			time.as_mut(),
-			BIG_POWERUP_DURATION,
-		);
-
-		let powerup = big_powerup_materials.add(BigPowerupMaterial::new(source_image.0.clone()));
 
-		// Add a post-processing effect
-		update_post_processing_effects(
-			&mut commands,
-			&screen,
-			powerup.clone(),
-			&mut meshes,
-			&post_processing_pass_layer,
-		);
-
-		active_effect.0 = Some(PowerupMaterial::BigPowerup(powerup));
-		AudioPlayer::play_sfx(
-			audio.as_ref(),
-			snorting_sounds
-				.choose(&mut rand::thread_rng())
-				.expect("No snorting sounds!")
-				.clone(),
-			PLAYER_SNORTING_VOLUME,
-			settings.as_ref(),
-		);
+	*stats.items_used.entry(item.id.clone()).or_default() += 1;
 
-		stats.big_powerup_used += 1;
-	}
-}
+	match &item.use_action {
+		Some(UseAction::Effect(effect)) => {
+			let definition = registry.get(effect).expect("Item effect not defined!");
+			effects.apply(definition, &registry, health.as_mut());
 
-fn update_powerup_material(
-	mut active_effect: ResMut<ActiveMaterial>,
-	mut small_powerup_materials: ResMut<Assets<SmallPowerupMaterial>>,
-	mut big_powerup_materials: ResMut<Assets<BigPowerupMaterial>>,
-	time: Res<TimeCounter>,
-) {
-	match &mut active_effect.0 {
-		Some(powerup) => match powerup {
-			PowerupMaterial::SmallPowerup(powerup) => {
-				let mut powerup = small_powerup_materials.get_mut(powerup).unwrap();
-
-				powerup.time = (time.seconds_since_startup() * 1000.0).floor() as u32;
-			}
-			PowerupMaterial::BigPowerup(powerup) => {
-				let mut powerup = big_powerup_materials.get_mut(powerup).unwrap();
-
-				powerup.time = (time.seconds_since_startup() * 1000.0).floor() as u32;
-			}
-		},
-		None => (),
-	}
-}
+			sounds.send(PlaySound::new(SoundId::Snorting));
+		}
+		Some(UseAction::Heal(amount)) => {
+			health.heal(*amount);
 
-fn pick_up_cocaine(
-	mut commands: Commands,
-	mut player_query: Query<(&mut Inventory, &Transform), With<Player>>,
-	cocaine_query: Query<(Entity, &Transform), With<Cocaine>>,
-	mut stats: ResMut<Stats>,
-) {
-	let (mut player_inventory, player_transform) = player_query.single_mut();
-
-	for (cocaine, cocaine_transform) in cocaine_query.iter() {
-		if (player_transform.translation.truncate() - cocaine_transform.translation.truncate())
-			.length()
-			<= TILE_SIZE / 2.0
-		{
-			player_inventory.add_small_powerup(1);
-			stats.small_powerup_collected += 1;
-			commands.entity(cocaine).despawn_recursive();
+			sounds.send(PlaySound::new(SoundId::Pickup));
 		}
+		None => (),
 	}
 }
 
-fn craft_magic_dust(
-	mut player_query: Query<&mut Inventory, With<Player>>,
-	keyboard: Res<Input<KeyCode>>,
-	audio: Res<Audio>,
-	settings: Res<Settings>,
-	crafting_sound: Res<CraftingSound>,
-	mut stats: ResMut<Stats>,
-) {
-	let mut inventory = player_query.single_mut();
-
-	// Press T to craft magic dust
-	#[allow(clippy::collapsible_if)]
-	if keyboard.just_pressed(KeyCode::T) {
-		if inventory.subtract_small_powerup(3) {
-			inventory.add_big_powerup(1);
-			AudioPlayer::play_sfx(
-				audio.as_ref(),
-				crafting_sound.clone(),
-				PLAYER_CRAFTING_VOLUME,
-				settings.as_ref()
-			);
-			stats.big_powerup_crafted += 1;
-		}
-	}
+/// The effects are dropped with the player, so they can't slow down the menus.
+fn reset_timescale(mut time: ResMut<TimeCounter>) {
+	time.clear_scale(TimeLayer::Powerup);
 }
 
 fn get_shot(
 	mut player_query: Query<(Entity, &mut Health), With<Player>>,
 	mut shot_events: EventReader<ShotEvent>,
+	mut damage_events: EventWriter<DamageEvent>,
 	mut state: ResMut<State<GameState>>,
-	mut stats: ResMut<Stats>
+	mut stats: ResMut<Stats>,
+	difficulty: Res<DifficultyScaling>,
+	mut rng: ResMut<GameRng>,
 ) {
 	let (player, mut health) = player_query.single_mut();
 
@@ -578,12 +518,22 @@ fn get_shot(
 			continue;
 		}
 
-		let damage = 25.0 + random::<f32>() * 10.0;
+		let damage = (25.0 + rng.gameplay().gen::<f32>() * 10.0) * difficulty.enemy_damage;
+
+		let health_before = health.get_health();
+		let died = health.take_damage(damage);
+
+		// The armor soaks some of it up, only what the health lost counts
+		stats.damage_taken += (health_before - health.get_health()).min(health_before);
 
-		stats.damage_taken += damage.min(health.get_health());
+		damage_events.send(DamageEvent {
+			target: player,
+			amount: damage,
+			source: shot.1,
+		});
 
 		#[allow(clippy::collapsible_if)]
-		if health.take_damage(damage) {
+		if died {
 			if state.set(GameState::GameOver).is_err() {}
 		}
 	}
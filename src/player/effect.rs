@@ -1,75 +1,352 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-	time::TimeCounter,
-	unit::{Effect, Health, Movement, Shooting},
+	time::{TimeCounter, TimeLayer},
+	unit::{modified_stat, BaseStats, Health, Movement, Shooting, Stat, StatModifier},
+	GameState,
 };
 
-#[derive(Component)]
-pub struct EffectData {
-	pub effect: Option<Box<dyn Effect + Send + Sync>>,
-	pub duration: Timer,
+use super::Player;
+
+/// What happens when an effect gets applied while it's still active.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Stacking {
+	/// Start the duration over
+	Refresh,
+	/// Add the duration to the time that's left
+	Extend,
+	/// Apply the modifiers once more, up to the given number of stacks, and start the duration over
+	Stack(u32),
+}
+
+/// Applied once, at the moment the effect starts.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum InstantEffect {
+	Heal(f32),
+}
+
+/// The post-processing shader shown while the effect is active.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum EffectShader {
+	SmallPowerup,
+	BigPowerup,
+	Comedown,
+	Overdose,
+}
+
+/// Which part of the trip an effect is, shown on the powerup bar.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Serialize, Deserialize)]
+pub enum EffectPhase {
+	Comedown,
+	#[default]
+	High,
+	Overdose,
+}
+
+/// Repeated doses within the window get weaker, and too many of them cause an overdose.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tolerance {
+	pub window: f32,
+	/// How much of its potency a dose loses for every other dose in the window
+	pub potency_loss: f32,
+	#[serde(default)]
+	pub overdose: Option<Overdose>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Overdose {
+	/// The number of doses in the window, including the new one, that cause an overdose
+	pub doses: u32,
+	pub effect: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EffectDefinition {
+	pub id: String,
+	pub duration: f32,
+	pub stacking: Stacking,
+	#[serde(default)]
+	pub phase: EffectPhase,
+	#[serde(default)]
+	pub modifiers: Vec<StatModifier>,
+	#[serde(default)]
+	pub on_apply: Vec<InstantEffect>,
+	/// Negative values drain health
+	#[serde(default)]
+	pub health_per_second: f32,
+	#[serde(default)]
+	pub shader: Option<EffectShader>,
+	#[serde(default)]
+	pub tolerance: Option<Tolerance>,
+	/// Effects that end when this one starts
+	#[serde(default)]
+	pub removes: Vec<String>,
+	/// Started with the same potency when this effect runs out
+	#[serde(default)]
+	pub then: Option<String>,
 }
 
-impl EffectData {
-	pub fn apply(
-		&mut self,
-		effect: Option<Box<dyn Effect + Send + Sync>>,
-		movement: &mut Movement,
-		health: &mut Health,
-		shooting: &mut Shooting,
-		time: &mut TimeCounter,
-		duration: f32,
-	) {
-		self.effect = effect;
-
-		if let Some(effect) = &mut self.effect {
-			effect.apply(movement, health, shooting, time);
+/// All the effects defined in `assets/effects/effects.ron`.
+pub struct EffectRegistry {
+	definitions: HashMap<String, EffectDefinition>,
+}
+
+impl EffectRegistry {
+	pub fn load() -> Self {
+		// Included in the binary like the level, so it works on the web as well
+		let definitions: Vec<EffectDefinition> =
+			ron::from_str(include_str!("../../assets/effects/effects.ron")).expect("Invalid effect definitions!");
+
+		Self {
+			definitions: definitions
+				.into_iter()
+				.map(|definition| (definition.id.clone(), definition))
+				.collect(),
 		}
+	}
 
-		self.duration = Timer::from_seconds(duration, false);
+	pub fn get(&self, id: &str) -> Option<&EffectDefinition> {
+		self.definitions.get(id)
 	}
+}
 
-	pub fn finish(
-		&mut self,
-		movement: &mut Movement,
-		health: &mut Health,
-		shooting: &mut Shooting,
-		time: &mut TimeCounter,
-	) {
-		if let Some(effect) = &self.effect {
-			effect.finish(movement, health, shooting, time);
-		}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActiveEffect {
+	pub id: String,
+	pub stacks: u32,
+	pub potency: f32,
+	pub duration: f32,
+	pub elapsed: f32,
+}
 
-		self.effect = None;
+impl ActiveEffect {
+	pub fn remaining(&self) -> f32 {
+		(self.duration - self.elapsed).max(0.0)
 	}
 }
 
-pub struct SmallPowerup;
+#[derive(Clone, Debug)]
+struct Dose {
+	id: String,
+	time: f32,
+}
+
+/// The effects currently affecting a unit.
+#[derive(Component, Default)]
+pub struct Effects {
+	active: Vec<ActiveEffect>,
+	doses: Vec<Dose>,
+	/// How long the effects have been ticking, used to tell how old the doses are
+	clock: f32,
+}
+
+impl Effects {
+	/// Takes a dose of an effect, which might be weakened by tolerance or cause an overdose.
+	pub fn apply(&mut self, definition: &EffectDefinition, registry: &EffectRegistry, health: &mut Health) {
+		let mut potency = 1.0;
+		let mut overdose = None;
+
+		if let Some(tolerance) = &definition.tolerance {
+			let clock = self.clock;
+			self.doses.retain(|dose| clock - dose.time <= tolerance.window);
+
+			let previous_doses = self.doses.iter().filter(|dose| dose.id == definition.id).count() as u32;
+			potency = (1.0 - tolerance.potency_loss).max(0.0).powi(previous_doses as i32);
+
+			overdose = tolerance
+				.overdose
+				.as_ref()
+				.filter(|overdose| previous_doses + 1 >= overdose.doses)
+				.and_then(|overdose| registry.get(&overdose.effect));
+
+			self.doses.push(Dose {
+				id: definition.id.clone(),
+				time: clock,
+			});
+		}
+
+		self.start(definition, potency, health);
+
+		if let Some(overdose) = overdose {
+			self.start(overdose, 1.0, health);
+		}
+	}
+
+	fn start(&mut self, definition: &EffectDefinition, potency: f32, health: &mut Health) {
+		self.active.retain(|active| !definition.removes.contains(&active.id));
 
-impl Effect for SmallPowerup {
-	fn apply(&self, movement: &mut Movement, health: &mut Health, _: &mut Shooting, _: &mut TimeCounter) {
-		movement.speed *= 2.0;
-		health.heal(35.0);
+		for instant in &definition.on_apply {
+			match instant {
+				InstantEffect::Heal(amount) => health.heal(*amount * potency),
+			}
+		}
+
+		if let Some(active) = self.active.iter_mut().find(|active| active.id == definition.id) {
+			match definition.stacking {
+				Stacking::Refresh => {
+					active.duration = definition.duration;
+				}
+				Stacking::Extend => {
+					active.duration = active.remaining() + definition.duration;
+				}
+				Stacking::Stack(max_stacks) => {
+					active.stacks = (active.stacks + 1).min(max_stacks);
+					active.duration = definition.duration;
+				}
+			}
+
+			active.potency = potency;
+			active.elapsed = 0.0;
+			return;
+		}
+
+		self.active.push(ActiveEffect {
+			id: definition.id.clone(),
+			stacks: 1,
+			potency,
+			duration: definition.duration,
+			elapsed: 0.0,
+		});
+	}
+
+	/// Advances all the effects and removes the ones that ran out.
+	///
+	/// # Returns
+	/// The effects that ran out.
+	pub fn tick(&mut self, delta: f32) -> Vec<ActiveEffect> {
+		self.clock += delta;
+
+		for active in &mut self.active {
+			active.elapsed += delta;
+		}
+
+		let (finished, active): (Vec<_>, Vec<_>) = self
+			.active
+			.drain(..)
+			.partition(|active| active.elapsed >= active.duration);
+
+		self.active = active;
+
+		finished
+	}
+
+	pub fn active(&self) -> &[ActiveEffect] {
+		&self.active
 	}
 
-	fn finish(&self, movement: &mut Movement, _: &mut Health, _: &mut Shooting, _: &mut TimeCounter) {
-		movement.speed /= 2.0;
+	/// Replaces all the effects, without applying their instant effects again.
+	pub fn restore(&mut self, active: Vec<ActiveEffect>) {
+		self.active = active;
+	}
+
+	/// Every modifier of every active effect, scaled by its potency and repeated once per stack.
+	pub fn modifiers<'a>(&'a self, registry: &'a EffectRegistry) -> impl Iterator<Item = StatModifier> + 'a {
+		self.active.iter().flat_map(move |active| {
+			let modifiers = registry
+				.get(&active.id)
+				.map(|definition| definition.modifiers.as_slice())
+				.unwrap_or_default();
+
+			(0..active.stacks).flat_map(move |_| {
+				modifiers
+					.iter()
+					.map(move |modifier| modifier.with_potency(active.potency))
+			})
+		})
+	}
+
+	/// The health all the effects together give or take every second.
+	pub fn health_per_second(&self, registry: &EffectRegistry) -> f32 {
+		self.active
+			.iter()
+			.filter_map(|active| {
+				let definition = registry.get(&active.id)?;
+
+				Some(definition.health_per_second * active.potency * active.stacks as f32)
+			})
+			.sum()
+	}
+
+	/// The shader of the most severe effect that has one, the most recently started one wins ties.
+	pub fn shader(&self, registry: &EffectRegistry) -> Option<EffectShader> {
+		self.active
+			.iter()
+			.filter_map(|active| {
+				let definition = registry.get(&active.id)?;
+
+				Some((definition.phase, definition.shader?))
+			})
+			.max_by_key(|(phase, _)| *phase)
+			.map(|(_, shader)| shader)
+	}
+
+	pub fn is_high(&self, registry: &EffectRegistry) -> bool {
+		self.active.iter().any(|active| {
+			registry
+				.get(&active.id)
+				.map_or(false, |definition| definition.phase == EffectPhase::High)
+		})
+	}
+
+	/// The most severe phase the unit is in, and how much of it is left, from 1 to 0.
+	pub fn phase(&self, registry: &EffectRegistry) -> Option<(EffectPhase, f32)> {
+		self.active
+			.iter()
+			.filter(|active| active.duration > 0.0)
+			.filter_map(|active| {
+				let phase = registry.get(&active.id)?.phase;
+
+				Some((phase, active.remaining() / active.duration))
+			})
+			.max_by(|(phase, remaining), (other_phase, other_remaining)| {
+				phase.cmp(other_phase).then(remaining.total_cmp(other_remaining))
+			})
 	}
 }
 
-pub struct BigPowerup;
+/// Ticks the effects, recomputes the player's stats from the base stats and the modifiers and drains health.
+pub fn update_effects(
+	mut player_query: Query<
+		(&BaseStats, &mut Effects, &mut Movement, &mut Shooting, &mut Health),
+		With<Player>,
+	>,
+	registry: Res<EffectRegistry>,
+	mut time: ResMut<TimeCounter>,
+	mut state: ResMut<State<GameState>>,
+) {
+	let (base_stats, mut effects, mut movement, mut shooting, mut health) = player_query.single_mut();
+
+	for finished in effects.tick(time.delta_seconds()) {
+		let next = registry
+			.get(&finished.id)
+			.and_then(|definition| definition.then.as_ref())
+			.and_then(|next| registry.get(next));
 
-impl Effect for BigPowerup {
-	fn apply(&self, movement: &mut Movement, _: &mut Health, shooting: &mut Shooting, time: &mut TimeCounter) {
-		movement.speed *= 3.0;
-		shooting.cooldown.set_duration(shooting.cooldown.duration() / 5);
-		time.timescale /= 3.0;
+		if let Some(next) = next {
+			effects.start(next, finished.potency, health.as_mut());
+		}
 	}
 
-	fn finish(&self, movement: &mut Movement, _: &mut Health, shooting: &mut Shooting, time: &mut TimeCounter) {
-		movement.speed /= 3.0;
-		shooting.cooldown.set_duration(shooting.cooldown.duration() * 5);
-		time.timescale *= 3.0;
+	movement.speed = modified_stat(base_stats.speed, Stat::MovementSpeed, effects.modifiers(&registry));
+
+	let cooldown = modified_stat(base_stats.weapon_cooldown, Stat::WeaponCooldown, effects.modifiers(&registry));
+	shooting.cooldown.set_duration(Duration::from_secs_f32(cooldown.max(0.0)));
+
+	time.set_scale(TimeLayer::Powerup, modified_stat(1.0, Stat::Timescale, effects.modifiers(&registry)));
+
+	let health_change = effects.health_per_second(&registry) * time.delta_seconds();
+
+	if health_change > 0.0 {
+		health.heal(health_change);
+	} else if health_change < 0.0 {
+		// Not counted as damage taken, only getting hurt by the enemies is
+		#[allow(clippy::collapsible_if)]
+		if health.lose_health(-health_change) {
+			if state.set(GameState::GameOver).is_err() {}
+		}
 	}
 }
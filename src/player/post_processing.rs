@@ -1,68 +1,390 @@
+use std::time::Duration;
+
 use bevy::{
 	prelude::*,
 	reflect::TypeUuid,
 	render::render_resource::{AsBindGroup, ShaderRef},
-	sprite::{Material2d, Material2dPlugin},
+	sprite::Material2d,
+};
+
+use crate::post_processing::{
+	spawn_pass, CameraRenderImage, PassLayers, PostProcessingMaterial, PostProcessingMaterialPlugin,
+	PostProcessingPass, ScreenQuad,
 };
+use crate::shader_params::ShaderTuning;
+use crate::time::TimeCounter;
+use crate::unit::{DamageEvent, Health};
+use crate::GameState;
+
+use super::effect::{EffectRegistry, EffectShader, Effects};
+use super::{Player, LOW_HEALTH};
+
+const DRUG_ORDER: u32 = 10;
+const DAMAGE_FLASH_ORDER: u32 = 20;
+const VIGNETTE_ORDER: u32 = 30;
 
-use crate::{post_processing::{
-	update_post_processing_effects, CameraRenderImage, DefaultMaterial, PostProcessingLayer, Screen,
-}, GameState};
+const COMEDOWN_INTENSITY: u32 = 40;
+const OVERDOSE_INTENSITY: u32 = 100;
+
+const DAMAGE_FLASH_DURATION: f32 = 0.3;
 
 pub struct PlayerPostProcessingPlugin;
 
 impl Plugin for PlayerPostProcessingPlugin {
 	fn build(&self, app: &mut App) {
 		app
-			.add_plugin(Material2dPlugin::<SmallPowerupMaterial>::default())
-			.add_plugin(Material2dPlugin::<BigPowerupMaterial>::default())
-			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(clean_post_processing_effect));
+			.add_plugin(PostProcessingMaterialPlugin::<SmallPowerupMaterial>::default())
+			.add_plugin(PostProcessingMaterialPlugin::<BigPowerupMaterial>::default())
+			.add_plugin(PostProcessingMaterialPlugin::<ComedownMaterial>::default())
+			.add_plugin(PostProcessingMaterialPlugin::<DamageFlashMaterial>::default())
+			.add_plugin(PostProcessingMaterialPlugin::<VignetteMaterial>::default())
+			.add_startup_system_to_stage(StartupStage::PostStartup, spawn_player_passes)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(update_drug_passes.label("update_drug_passes").after("use_item"))
+					.with_system(update_drug_materials.after("update_drug_passes"))
+					.with_system(update_damage_flash)
+					.with_system(update_vignette),
+			)
+			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(disable_player_passes));
 	}
 }
 
-pub fn clean_post_processing_effect(
+/// The pass of the shader of a drug effect, enabled while the effects of the player want that shader.
+#[derive(Component)]
+struct DrugPass(EffectShader);
+
+#[derive(Component)]
+struct DamageFlashPass {
+	timer: Timer,
+}
+
+#[derive(Component)]
+struct VignettePass;
+
+fn spawn_player_passes(
 	mut commands: Commands,
-	screen: Query<Entity, With<Screen>>,
-	mut meshes: ResMut<Assets<Mesh>>,
-	post_processing_pass_layer: Res<PostProcessingLayer>,
+	mut layers: ResMut<PassLayers>,
+	screen_quad: Res<ScreenQuad>,
 	source_image: Res<CameraRenderImage>,
-	mut materials: ResMut<Assets<DefaultMaterial>>,
+	mut small_powerup_materials: ResMut<Assets<SmallPowerupMaterial>>,
+	mut big_powerup_materials: ResMut<Assets<BigPowerupMaterial>>,
+	mut comedown_materials: ResMut<Assets<ComedownMaterial>>,
+	mut damage_flash_materials: ResMut<Assets<DamageFlashMaterial>>,
+	mut vignette_materials: ResMut<Assets<VignetteMaterial>>,
+	tuning: Res<ShaderTuning>,
+) {
+	let disabled = |order| PostProcessingPass { order, enabled: false };
+	let source = || source_image.0.clone();
+
+	let small_powerup = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		small_powerup_materials.add(SmallPowerupMaterial::new(source(), tuning.small_powerup)),
+		disabled(DRUG_ORDER),
+		"SmallPowerupPass",
+	);
+
+	let big_powerup = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		big_powerup_materials.add(BigPowerupMaterial::new(source(), tuning.big_powerup)),
+		disabled(DRUG_ORDER),
+		"BigPowerupPass",
+	);
+
+	let comedown = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		comedown_materials.add(ComedownMaterial::new(source(), COMEDOWN_INTENSITY)),
+		disabled(DRUG_ORDER),
+		"ComedownPass",
+	);
+
+	let overdose = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		comedown_materials.add(ComedownMaterial::new(source(), OVERDOSE_INTENSITY)),
+		disabled(DRUG_ORDER),
+		"OverdosePass",
+	);
+
+	commands.entity(small_powerup).insert(DrugPass(EffectShader::SmallPowerup));
+	commands.entity(big_powerup).insert(DrugPass(EffectShader::BigPowerup));
+	commands.entity(comedown).insert(DrugPass(EffectShader::Comedown));
+	commands.entity(overdose).insert(DrugPass(EffectShader::Overdose));
+
+	let damage_flash = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		damage_flash_materials.add(DamageFlashMaterial::new(source())),
+		disabled(DAMAGE_FLASH_ORDER),
+		"DamageFlashPass",
+	);
+
+	let mut timer = Timer::from_seconds(DAMAGE_FLASH_DURATION, false);
+	timer.tick(Duration::from_secs_f32(DAMAGE_FLASH_DURATION));
+
+	commands.entity(damage_flash).insert(DamageFlashPass { timer });
+
+	let vignette = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		vignette_materials.add(VignetteMaterial::new(source())),
+		disabled(VIGNETTE_ORDER),
+		"VignettePass",
+	);
+
+	commands.entity(vignette).insert(VignettePass);
+}
+
+/// Enables the pass of the shader the active effects want, only one drug shader is shown at a time.
+fn update_drug_passes(
+	player_query: Query<&Effects, With<Player>>,
+	mut pass_query: Query<(&mut PostProcessingPass, &DrugPass)>,
+	registry: Res<EffectRegistry>,
 ) {
-	clean_post_processing(&mut commands, &screen.single(), &mut meshes, &post_processing_pass_layer, &source_image, &mut materials)
+	let shader = player_query.single().shader(&registry);
+
+	for (mut pass, drug) in pass_query.iter_mut() {
+		let enabled = shader == Some(drug.0);
+
+		if pass.enabled != enabled {
+			pass.enabled = enabled;
+		}
+	}
 }
 
-pub fn clean_post_processing(
-	commands: &mut Commands,
-	screen: &Entity,
-	meshes: &mut ResMut<Assets<Mesh>>,
-	post_processing_pass_layer: &Res<PostProcessingLayer>,
-	source_image: &Res<CameraRenderImage>,
-	default_materials: &mut ResMut<Assets<DefaultMaterial>>,
+/// Only writes the materials of the enabled passes, and only when something changed,
+/// since every material that gets written is sent to the GPU again.
+fn update_drug_materials(
+	small_powerup_query: Query<(&PostProcessingPass, &Handle<SmallPowerupMaterial>)>,
+	big_powerup_query: Query<(&PostProcessingPass, &Handle<BigPowerupMaterial>)>,
+	comedown_query: Query<(&PostProcessingPass, &Handle<ComedownMaterial>)>,
+	mut small_powerup_materials: ResMut<Assets<SmallPowerupMaterial>>,
+	mut big_powerup_materials: ResMut<Assets<BigPowerupMaterial>>,
+	mut comedown_materials: ResMut<Assets<ComedownMaterial>>,
+	tuning: Res<ShaderTuning>,
+	time: Res<TimeCounter>,
 ) {
-	let material = DefaultMaterial {
-		source_image: source_image.0.clone(),
-	};
-
-	let material_handle = default_materials.add(material);
-
-	update_post_processing_effects(
-		commands,
-		screen,
-		material_handle,
-		meshes,
-		post_processing_pass_layer,
-	);
+	let seconds = time.seconds_since_startup();
+
+	let params = tuning.small_powerup.at(seconds);
+
+	for (_, handle) in small_powerup_query.iter().filter(|(pass, _)| pass.enabled) {
+		if small_powerup_materials.get(handle).map_or(false, |material| material.params != params) {
+			if let Some(material) = small_powerup_materials.get_mut(handle) {
+				material.params = params;
+			}
+		}
+	}
+
+	let params = tuning.big_powerup.at(seconds);
+
+	for (_, handle) in big_powerup_query.iter().filter(|(pass, _)| pass.enabled) {
+		if big_powerup_materials.get(handle).map_or(false, |material| material.params != params) {
+			if let Some(material) = big_powerup_materials.get_mut(handle) {
+				material.params = params;
+			}
+		}
+	}
+
+	let comedown_time = (seconds * 1000.0).floor() as u32;
+
+	for (_, handle) in comedown_query.iter().filter(|(pass, _)| pass.enabled) {
+		if comedown_materials.get(handle).map_or(false, |material| material.time != comedown_time) {
+			if let Some(material) = comedown_materials.get_mut(handle) {
+				material.time = comedown_time;
+			}
+		}
+	}
+}
+
+/// Flashes the screen red when the player gets shot, in real time so it still fades out during the hit-stop.
+fn update_damage_flash(
+	player_query: Query<Entity, With<Player>>,
+	mut pass_query: Query<(&mut PostProcessingPass, &mut DamageFlashPass, &Handle<DamageFlashMaterial>)>,
+	mut damage_events: EventReader<DamageEvent>,
+	mut materials: ResMut<Assets<DamageFlashMaterial>>,
+	time: Res<TimeCounter>,
+) {
+	let player = player_query.single();
+	let hit = damage_events.iter().any(|damage| damage.target == player);
+
+	for (mut pass, mut flash, material) in pass_query.iter_mut() {
+		if hit {
+			flash.timer.reset();
+		}
+
+		flash.timer.tick(time.real_delta());
+
+		let enabled = !flash.timer.finished();
+
+		if pass.enabled != enabled {
+			pass.enabled = enabled;
+		}
+
+		if let Some(material) = materials.get_mut(material).filter(|_| enabled) {
+			material.intensity = ((1.0 - flash.timer.percent()) * 100.0) as u32;
+		}
+	}
+}
+
+/// Darkens the edges of the screen when the player is low on health, stronger the closer they are to dying.
+fn update_vignette(
+	player_query: Query<&Health, With<Player>>,
+	mut pass_query: Query<(&mut PostProcessingPass, &Handle<VignetteMaterial>), With<VignettePass>>,
+	mut materials: ResMut<Assets<VignetteMaterial>>,
+) {
+	let health = player_query.single();
+	let ratio = (health.get_health() / health.get_max_health()).max(0.0);
+	let intensity = ((1.0 - ratio / LOW_HEALTH) * 100.0).round().max(0.0) as u32;
+
+	for (mut pass, material) in pass_query.iter_mut() {
+		let enabled = intensity > 0;
+
+		if pass.enabled != enabled {
+			pass.enabled = enabled;
+		}
+
+		if let Some(material) = materials.get_mut(material).filter(|material| material.intensity != intensity) {
+			material.intensity = intensity;
+		}
+	}
+}
+
+fn disable_player_passes(
+	mut pass_query: Query<
+		&mut PostProcessingPass,
+		Or<(With<DrugPass>, With<DamageFlashPass>, With<VignettePass>)>,
+	>,
+) {
+	for mut pass in pass_query.iter_mut() {
+		pass.enabled = false;
+	}
 }
 
 #[derive(AsBindGroup, TypeUuid, Clone)]
 #[uuid = "18f452b0-0efc-497e-96fb-48af7b45731c"]
 pub struct SmallPowerupMaterial {
-	/// In this example, this image will be the result of the main camera.
+	/// The image rendered by the previous pass
+	#[texture(0)]
+	#[sampler(1)]
+	pub source_image: Handle<Image>,
+	#[uniform(2)]
+	pub params: ShaderParams,
+}
+
+impl Material2d for SmallPowerupMaterial {
+	fn fragment_shader() -> ShaderRef {
+		"shaders/small_drug.wgsl".into()
+	}
+}
+
+impl PostProcessingMaterial for SmallPowerupMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
+
+impl SmallPowerupMaterial {
+	pub fn new(source_image: Handle<Image>, params: ShaderParams) -> Self {
+		Self { source_image, params }
+	}
+}
+
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "2cf6b4dc-23f2-11ed-861d-0242ac120002"]
+pub struct BigPowerupMaterial {
+	/// The image rendered by the previous pass
+	#[texture(0)]
+	#[sampler(1)]
+	pub source_image: Handle<Image>,
+	#[uniform(2)]
+	pub params: ShaderParams,
+}
+
+impl Material2d for BigPowerupMaterial {
+	fn fragment_shader() -> ShaderRef {
+		"shaders/big_drug.wgsl".into()
+	}
+}
+
+impl PostProcessingMaterial for BigPowerupMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
+
+impl BigPowerupMaterial {
+	pub fn new(source_image: Handle<Image>, params: ShaderParams) -> Self {
+		Self { source_image, params }
+	}
+}
+
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "6d0f2b8e-52c4-4a4b-9a3e-1f7c2d9e8b41"]
+pub struct ComedownMaterial {
 	#[texture(0)]
 	#[sampler(1)]
 	pub source_image: Handle<Image>,
 	#[uniform(2)]
 	pub time: u32,
+	/// From 0 to 100, how blurry and grey the screen gets
+	#[uniform(2)]
+	pub intensity: u32,
+	#[uniform(2)]
+	pub _wasm_padding_0: u32,
+	#[uniform(2)]
+	pub _wasm_padding_1: u32,
+}
+
+impl Material2d for ComedownMaterial {
+	fn fragment_shader() -> ShaderRef {
+		"shaders/comedown.wgsl".into()
+	}
+}
+
+impl PostProcessingMaterial for ComedownMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
+
+impl ComedownMaterial {
+	pub fn new(source_image: Handle<Image>, intensity: u32) -> Self {
+		Self {
+			source_image,
+			time: 0,
+			intensity,
+			_wasm_padding_0: 0,
+			_wasm_padding_1: 0,
+		}
+	}
+}
+
+/// Tints the screen red for a moment after the player gets hit.
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "4a1c8e3f-7b2d-4f6a-8c9e-0d5b3a7e2f18"]
+pub struct DamageFlashMaterial {
+	#[texture(0)]
+	#[sampler(1)]
+	pub source_image: Handle<Image>,
+	/// From 0 to 100
+	#[uniform(2)]
+	pub intensity: u32,
 	#[uniform(2)]
 	pub _wasm_padding_0: u32,
 	#[uniform(2)]
@@ -71,17 +393,23 @@ pub struct SmallPowerupMaterial {
 	pub _wasm_padding_2: u32,
 }
 
-impl Material2d for SmallPowerupMaterial {
+impl Material2d for DamageFlashMaterial {
 	fn fragment_shader() -> ShaderRef {
-		"shaders/small_drug.wgsl".into()
+		"shaders/damage_flash.wgsl".into()
 	}
 }
 
-impl SmallPowerupMaterial {
+impl PostProcessingMaterial for DamageFlashMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
+
+impl DamageFlashMaterial {
 	pub fn new(source_image: Handle<Image>) -> Self {
 		Self {
 			source_image,
-			time: 0,
+			intensity: 0,
 			_wasm_padding_0: 0,
 			_wasm_padding_1: 0,
 			_wasm_padding_2: 0,
@@ -89,15 +417,16 @@ impl SmallPowerupMaterial {
 	}
 }
 
+/// Darkens the edges of the screen.
 #[derive(AsBindGroup, TypeUuid, Clone)]
-#[uuid = "2cf6b4dc-23f2-11ed-861d-0242ac120002"]
-pub struct BigPowerupMaterial {
-	/// In this example, this image will be the result of the main camera.
+#[uuid = "e8d2f6b4-1a3c-4e7f-b5d9-6c0a2f8e4b37"]
+pub struct VignetteMaterial {
 	#[texture(0)]
 	#[sampler(1)]
 	pub source_image: Handle<Image>,
+	/// From 0 to 100
 	#[uniform(2)]
-	pub time: u32,
+	pub intensity: u32,
 	#[uniform(2)]
 	pub _wasm_padding_0: u32,
 	#[uniform(2)]
@@ -106,20 +435,26 @@ pub struct BigPowerupMaterial {
 	pub _wasm_padding_2: u32,
 }
 
-impl Material2d for BigPowerupMaterial {
+impl Material2d for VignetteMaterial {
 	fn fragment_shader() -> ShaderRef {
-		"shaders/big_drug.wgsl".into()
+		"shaders/vignette.wgsl".into()
 	}
 }
 
-impl BigPowerupMaterial {
+impl PostProcessingMaterial for VignetteMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
+
+impl VignetteMaterial {
 	pub fn new(source_image: Handle<Image>) -> Self {
 		Self {
 			source_image,
-			time: 0,
+			intensity: 0,
 			_wasm_padding_0: 0,
 			_wasm_padding_1: 0,
 			_wasm_padding_2: 0,
 		}
 	}
-}
\ No newline at end of file
+}
@@ -1,8 +1,8 @@
 use bevy::prelude::*;
 
-use crate::{unit::{Health, Inventory}, fonts::{PaintFont, RobotoFont}, stats::Stats};
+use crate::{unit::{Health, Inventory}, fonts::{PaintFont, RobotoFont}, stats::Stats, item::ItemRegistry};
 
-use super::{Player, effect::EffectData};
+use super::{Player, effect::{EffectPhase, EffectRegistry, Effects}};
 
 
 #[derive(Component)]
@@ -12,10 +12,14 @@ pub struct PlayerUi;
 pub struct HealthBar;
 
 #[derive(Component)]
-pub struct SmallPowerUpCounterNumber;
+pub struct ArmorBarContainer;
 
 #[derive(Component)]
-pub struct BigPowerUpCounterNumber;
+pub struct ArmorBar;
+
+/// Filled with a slot for every stack in the player's inventory
+#[derive(Component)]
+pub struct InventorySlots;
 
 #[derive(Component)]
 pub struct PowerupBarContainer;
@@ -26,9 +30,20 @@ pub struct PowerupBar;
 #[derive(Component)]
 pub struct LevelTimerUI;
 
+/// Compares the time at a checkpoint to the ghost's, empty until a checkpoint is reached
+#[derive(Component)]
+pub struct SplitTimeUI;
+
+fn phase_color(phase: EffectPhase) -> Color {
+    match phase {
+        EffectPhase::High => Color::WHITE,
+        EffectPhase::Comedown => Color::rgb(0.35, 0.45, 0.8),
+        EffectPhase::Overdose => Color::rgb(0.9, 0.1, 0.1),
+    }
+}
 
-pub fn ui_setup(mut commands: Commands, font: Res<PaintFont>, roboto_font: Res<RobotoFont>,) {
-    let font = &font.0;
+
+pub fn ui_setup(mut commands: Commands, roboto_font: Res<RobotoFont>,) {
     let roboto_font = &roboto_font.0;
 
     commands
@@ -104,12 +119,42 @@ pub fn ui_setup(mut commands: Commands, font: Res<PaintFont>, roboto_font: Res<R
                                         .insert(Name::new("HealthBar"))
                                         .insert(HealthBar);
                                 });
+
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Percent(100.0), Val::Px(18.0)),
+                                        padding: UiRect::all(Val::Px(5.0)),
+                                        display: Display::None,
+                                        ..Default::default()
+                                    },
+                                    color: Color::BLACK.into(),
+                                    ..Default::default()
+                                })
+                                .insert(Name::new("ArmorBarContainer"))
+                                .insert(ArmorBarContainer)
+                                .with_children(|parent| {
+                                    parent
+                                        .spawn_bundle(NodeBundle {
+                                            style: Style {
+                                                size: Size::new(
+                                                    Val::Percent(0.0),
+                                                    Val::Percent(100.0),
+                                                ),
+                                                ..Default::default()
+                                            },
+                                            color: Color::rgb(0.45, 0.6, 0.85).into(),
+                                            ..Default::default()
+                                        })
+                                        .insert(Name::new("ArmorBar"))
+                                        .insert(ArmorBar);
+                                });
                         });
 
                     parent
                         .spawn_bundle(NodeBundle {
                             style: Style {
-                                size: Size::new(Val::Px(240.0), Val::Percent(20.0)),
+                                size: Size::new(Val::Px(320.0), Val::Percent(20.0)),
                                 flex_direction: FlexDirection::ColumnReverse,
                                 ..Default::default()
                             },
@@ -117,93 +162,7 @@ pub fn ui_setup(mut commands: Commands, font: Res<PaintFont>, roboto_font: Res<R
                             ..Default::default()
                         })
                         .insert(Name::new("Inventory"))
-                        .with_children(|parent| {
-                            parent
-                                .spawn_bundle(
-                                    TextBundle::from_section(
-                                        "Cocaine: ",
-                                        TextStyle {
-                                            font: font.clone(),
-                                            font_size: 32.0,
-                                            color: Color::PINK, // TODO: Give it a sensible color (and maybe change the font)
-                                        },
-                                    )
-                                    .with_style(Style {
-                                        size: Size::new(Val::Auto, Val::Auto),
-                                        margin: UiRect::all(Val::Px(0.0)),
-                                        ..default()
-                                    }),
-                                )
-                                .insert(Name::new("SmallPowerUpCounter"))
-                                .with_children(|parent| {
-                                    parent
-                                        .spawn_bundle(
-                                            TextBundle::from_section(
-                                                "0",
-                                                TextStyle {
-                                                    font: font.clone(),
-                                                    font_size: 32.0,
-                                                    color: Color::PINK, // this needs changing
-                                                },
-                                            )
-                                            .with_style(Style {
-                                                size: Size::new(Val::Auto, Val::Auto),
-                                                margin: UiRect::new(
-                                                    Val::Px(115.0),
-                                                    Val::Px(0.0),
-                                                    Val::Px(0.0),
-                                                    Val::Px(0.0),
-                                                ),
-                                                ..default()
-                                            }),
-                                        )
-                                        .insert(Name::new("SmallPowerUpCounterNumber"))
-                                        .insert(SmallPowerUpCounterNumber);
-                                });
-
-                            parent
-                                .spawn_bundle(
-                                    TextBundle::from_section(
-                                        "Fun Dust: ",
-                                        TextStyle {
-                                            font: font.clone(),
-                                            font_size: 32.0,
-                                            color: Color::PINK,
-                                        },
-                                    )
-                                    .with_style(Style {
-                                        size: Size::new(Val::Auto, Val::Auto),
-                                        margin: UiRect::all(Val::Px(0.0)),
-                                        ..default()
-                                    }),
-                                )
-                                .insert(Name::new("BigPowerUpCounter"))
-                                .with_children(|parent| {
-                                    parent
-                                        .spawn_bundle(
-                                            TextBundle::from_section(
-                                                "0",
-                                                TextStyle {
-                                                    font: font.clone(),
-                                                    font_size: 32.0,
-                                                    color: Color::PINK, // this needs changing
-                                                },
-                                            )
-                                            .with_style(Style {
-                                                size: Size::new(Val::Auto, Val::Auto),
-                                                margin: UiRect::new(
-                                                    Val::Px(155.0),
-                                                    Val::Px(0.0),
-                                                    Val::Px(0.0),
-                                                    Val::Px(0.0),
-                                                ),
-                                                ..default()
-                                            }),
-                                        )
-                                        .insert(Name::new("BigPowerUpCounterNumber"))
-                                        .insert(BigPowerUpCounterNumber);
-                                });
-                        });
+                        .insert(InventorySlots);
                 });
 
             parent
@@ -302,79 +261,78 @@ pub fn ui_setup(mut commands: Commands, font: Res<PaintFont>, roboto_font: Res<R
                     )
                     .insert(Name::new("Timer"))
                     .insert(LevelTimerUI);
+
+                    parent.spawn_bundle(
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: roboto_font.clone(),
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                            },
+                        )
+                    )
+                    .insert(Name::new("SplitTime"))
+                    .insert(SplitTimeUI);
                 });
         });
 }
 
+/// Only shown while the player has some armor.
+pub fn update_armor_bar(
+    player_query: Query<&Health, With<Player>>,
+    mut container_query: Query<&mut Style, With<ArmorBarContainer>>,
+    mut armor_bar_query: Query<&mut Style, (With<ArmorBar>, Without<ArmorBarContainer>)>,
+) {
+    let health = player_query.single();
+    let mut container = container_query.single_mut();
+    let mut armor_bar = armor_bar_query.single_mut();
+
+    if health.get_armor() > 0.0 && health.get_max_armor() > 0.0 {
+        container.display = Display::Flex;
+        armor_bar.size.width = Val::Percent(health.get_armor() / health.get_max_armor() * 100.0);
+    } else {
+        container.display = Display::None;
+    }
+}
+
 pub fn drop_ui(mut commands: Commands, ui_query: Query<Entity, With<PlayerUi>>) {
     let ui = ui_query.single();
     commands.entity(ui).despawn_recursive();
 }
 
 pub fn update_ui(
-    player_query: Query<(&Health, &Inventory, &EffectData), With<Player>>,
+    player_query: Query<(&Health, &Effects), With<Player>>,
     mut health_bar_query: Query<&mut Style, With<HealthBar>>,
-    mut small_powerup_counter_query: Query<
-        &mut Text,
-        (
-            With<SmallPowerUpCounterNumber>,
-            Without<BigPowerUpCounterNumber>,
-            Without<LevelTimerUI>,
-        ),
-    >,
-    mut big_powerup_counter_query: Query<
-        &mut Text,
-        (
-            With<BigPowerUpCounterNumber>,
-            Without<SmallPowerUpCounterNumber>,
-            Without<LevelTimerUI>,
-        ),
-    >,
     mut powerup_bar_container_query: Query<
         &mut Style,
         (With<PowerupBarContainer>, Without<HealthBar>),
     >,
     mut powerup_bar_query: Query<
-        &mut Style,
+        (&mut Style, &mut UiColor),
         (
             With<PowerupBar>,
             Without<PowerupBarContainer>,
             Without<HealthBar>,
         ),
     >,
-    mut level_timer_ui_query: Query<
-    &mut Text,
-    (
-        With<LevelTimerUI>,
-        Without<SmallPowerUpCounterNumber>,
-        Without<BigPowerUpCounterNumber>,
-    )
-    >,
+    mut level_timer_ui_query: Query<&mut Text, With<LevelTimerUI>>,
     stats: Res<Stats>,
+    registry: Res<EffectRegistry>,
 ) {
-    let (player_health, inventory, effect_data) = player_query.single();
+    let (player_health, effects) = player_query.single();
 
     let mut health_bar_style = health_bar_query.single_mut();
     health_bar_style.size.width =
         Val::Percent(player_health.get_health() / player_health.get_max_health() * 100.0);
 
-    let mut small_powerup_counter = small_powerup_counter_query.single_mut();
-    small_powerup_counter.sections[0].value = inventory.get_small_powerup_quantity().to_string();
-
-    let mut big_powerup_counter = big_powerup_counter_query.single_mut();
-    big_powerup_counter.sections[0].value = inventory.get_big_powerup_quantity().to_string();
-
     let mut powerup_bar_container = powerup_bar_container_query.single_mut();
-    let mut powerup_bar = powerup_bar_query.single_mut();
+    let (mut powerup_bar, mut powerup_bar_color) = powerup_bar_query.single_mut();
 
-    if effect_data.effect.is_some() {
+    if let Some((phase, remaining)) = effects.phase(&registry) {
         powerup_bar_container.display = Display::Flex;
-        powerup_bar.size.width = Val::Percent(
-            100.0
-                * (1.0
-                    - effect_data.duration.elapsed_secs()
-                        / effect_data.duration.duration().as_secs_f32()),
-        );
+        powerup_bar.size.width = Val::Percent(100.0 * remaining);
+        *powerup_bar_color = phase_color(phase).into();
     } else {
         powerup_bar_container.display = Display::None;
     }
@@ -382,3 +340,67 @@ pub fn update_ui(
     let mut level_timer_ui = level_timer_ui_query.single_mut();
     level_timer_ui.sections[0].value = format!("{:.2}", stats.timer.elapsed_secs());
 }
+
+/// Rebuilds the inventory slots whenever the contents of the inventory change.
+pub fn update_inventory_slots(
+    mut commands: Commands,
+    player_query: Query<&Inventory, (With<Player>, Changed<Inventory>)>,
+    slots_query: Query<Entity, With<InventorySlots>>,
+    items: Res<ItemRegistry>,
+    asset_server: Res<AssetServer>,
+    font: Res<PaintFont>,
+) {
+    let inventory = match player_query.get_single() {
+        Ok(inventory) => inventory,
+        Err(_) => return,
+    };
+
+    let slots = slots_query.single();
+
+    commands.entity(slots).despawn_descendants();
+
+    commands.entity(slots).with_children(|parent| {
+        for stack in inventory.items() {
+            let item = match items.get(&stack.id) {
+                Some(item) => item,
+                None => continue,
+            };
+
+            let label = match item.hotkey {
+                Some(hotkey) => format!("{}: {} [{}]", item.name, stack.quantity, hotkey),
+                None => format!("{}: {}", item.name, stack.quantity),
+            };
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .insert(Name::new(format!("{}Slot", item.name)))
+                .with_children(|parent| {
+                    parent.spawn_bundle(ImageBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(32.0), Val::Px(32.0)),
+                            margin: UiRect::new(Val::Px(0.0), Val::Px(8.0), Val::Px(0.0), Val::Px(0.0)),
+                            ..Default::default()
+                        },
+                        image: asset_server.load(&item.icon).into(),
+                        ..Default::default()
+                    });
+
+                    parent.spawn_bundle(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font: font.0.clone(),
+                            font_size: 32.0,
+                            color: Color::PINK, // TODO: Give it a sensible color (and maybe change the font)
+                        },
+                    ));
+                });
+        }
+    });
+}
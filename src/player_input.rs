@@ -0,0 +1,99 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::item::key_code;
+
+/// Everything the player does in a frame.
+/// The game reads this instead of the keyboard and the mouse, so a replay can take over by overwriting it.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PlayerInput {
+	/// Letters and digits held down, uppercase
+	pressed: Vec<char>,
+	just_pressed: Vec<char>,
+	/// The left mouse button was just pressed
+	shoot: bool,
+	/// On the window, measured like `Window::cursor_position`
+	cursor: Option<Vec2>,
+}
+
+impl PlayerInput {
+	pub fn pressed(&self, key: char) -> bool {
+		self.pressed.contains(&key.to_ascii_uppercase())
+	}
+
+	pub fn just_pressed(&self, key: char) -> bool {
+		self.just_pressed.contains(&key.to_ascii_uppercase())
+	}
+
+	pub fn shoot(&self) -> bool {
+		self.shoot
+	}
+
+	pub fn cursor(&self) -> Option<Vec2> {
+		self.cursor
+	}
+}
+
+/// Lets the tests play the game without a keyboard and a mouse.
+#[cfg(test)]
+impl PlayerInput {
+	pub fn press(&mut self, key: char) {
+		let key = key.to_ascii_uppercase();
+
+		if !self.pressed.contains(&key) {
+			self.pressed.push(key);
+			self.just_pressed.push(key);
+		}
+	}
+
+	pub fn set_shoot(&mut self, shoot: bool) {
+		self.shoot = shoot;
+	}
+
+	pub fn set_cursor(&mut self, cursor: Option<Vec2>) {
+		self.cursor = cursor;
+	}
+
+	/// Clears what only lasts for a single frame.
+	pub fn end_frame(&mut self) {
+		self.just_pressed.clear();
+		self.shoot = false;
+	}
+}
+
+pub struct PlayerInputPlugin;
+
+impl Plugin for PlayerInputPlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(PlayerInput::default()).add_system_to_stage(
+			CoreStage::PreUpdate,
+			sample_input.label("sample_input").after(InputSystem),
+		);
+	}
+}
+
+fn sample_input(
+	keyboard: Res<Input<KeyCode>>,
+	buttons: Res<Input<MouseButton>>,
+	windows: Res<Windows>,
+	mut input: ResMut<PlayerInput>,
+) {
+	input.pressed.clear();
+	input.just_pressed.clear();
+
+	for key in ('A'..='Z').chain('0'..='9') {
+		if let Some(key_code) = key_code(key) {
+			if keyboard.pressed(key_code) {
+				input.pressed.push(key);
+			}
+
+			if keyboard.just_pressed(key_code) {
+				input.just_pressed.push(key);
+			}
+		}
+	}
+
+	input.shoot = buttons.just_pressed(MouseButton::Left);
+	input.cursor = windows.get_primary().and_then(|window| window.cursor_position());
+}
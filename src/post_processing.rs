@@ -1,3 +1,5 @@
+use std::marker::PhantomData;
+
 use bevy::{
 	prelude::*,
 	reflect::TypeUuid,
@@ -11,47 +13,101 @@ use bevy::{
 		view::RenderLayers,
 	},
 	sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle},
+	window::{WindowId, WindowResized},
 };
 
+use crate::camera::CameraController;
+use crate::settings::Settings;
 use crate::{HEIGHT, WIDTH};
 
+/// Orders of the passes that are always there, the others go in between
+const COLORBLIND_FILTER_ORDER: u32 = 1000;
+const OUTPUT_ORDER: u32 = u32::MAX;
+
 pub struct PostProcessingPlugin;
 
 impl Plugin for PostProcessingPlugin {
 	fn build(&self, app: &mut App) {
-		app.add_plugin(Material2dPlugin::<DefaultMaterial>::default())
-			.add_startup_system(setup);
+		app.add_plugin(PostProcessingMaterialPlugin::<DefaultMaterial>::default())
+			.add_plugin(PostProcessingMaterialPlugin::<ColorblindMaterial>::default())
+			.add_startup_system(setup)
+			.add_startup_system_to_stage(StartupStage::PostStartup, spawn_global_passes)
+			.add_system(resize_render_target)
+			.add_system(update_colorblind_filter)
+			.add_system_to_stage(CoreStage::PostUpdate, route_passes.label("route_passes"));
+	}
+}
+
+/// A material that post-processes the image rendered by the pass before it.
+pub trait PostProcessingMaterial: Material2d {
+	fn source_image(&mut self) -> &mut Handle<Image>;
+}
+
+/// Registers the material and keeps its source image pointed at the output of the previous pass.
+pub struct PostProcessingMaterialPlugin<M>(PhantomData<M>);
+
+impl<M> Default for PostProcessingMaterialPlugin<M> {
+	fn default() -> Self {
+		Self(PhantomData)
+	}
+}
+
+impl<M: PostProcessingMaterial> Plugin for PostProcessingMaterialPlugin<M>
+where
+	M::Data: PartialEq + Eq + std::hash::Hash + Clone,
+{
+	fn build(&self, app: &mut App) {
+		app.add_plugin(Material2dPlugin::<M>::default())
+			.add_system_to_stage(CoreStage::PostUpdate, update_pass_source::<M>.after("route_passes"));
 	}
 }
 
 #[derive(Component)]
 pub struct MainCamera;
 
+/// One step of the post-processing chain. The enabled passes are drawn in order,
+/// each one reading what the one before it rendered.
 #[derive(Component)]
-pub struct Screen;
+pub struct PostProcessingPass {
+	pub order: u32,
+	pub enabled: bool,
+}
 
-#[derive(Deref, DerefMut)]
-pub struct PostProcessingLayer(pub RenderLayers);
+/// The image the pass reads from, set by `route_passes`.
+#[derive(Component)]
+struct PassInput(Handle<Image>);
+
+/// The camera that renders the quad of the pass.
+#[derive(Component)]
+struct PassCamera(Entity);
+
+#[derive(Component)]
+struct ColorblindPass;
 
 #[derive(Deref, DerefMut)]
 pub struct CameraRenderImage(pub Handle<Image>);
 
+/// The two images the passes take turns rendering into, so a pass never reads the image it's drawing to.
+struct PingPongImages([Handle<Image>; 2]);
+
+/// The full-screen quad the post processing materials are drawn on. It gets rebuilt whenever the window is resized.
 #[derive(Deref, DerefMut)]
-pub struct ScreenRes(pub Entity);
+pub struct ScreenQuad(pub Handle<Mesh>);
 
-fn setup(
-	mut commands: Commands,
-	mut meshes: ResMut<Assets<Mesh>>,
-	mut post_processing_materials: ResMut<Assets<DefaultMaterial>>,
-	mut images: ResMut<Assets<Image>>,
-) {
-	let size = Extent3d {
-		width: WIDTH as u32,
-		height: HEIGHT as u32,
+/// Every pass gets its own render layer, so its camera only sees its own quad.
+pub struct PassLayers {
+	next: u8,
+}
+
+fn render_target_size(width: f32, height: f32) -> Extent3d {
+	Extent3d {
+		width: (width as u32).max(1),
+		height: (height as u32).max(1),
 		..Default::default()
-	};
+	}
+}
 
-	// This is the texture that will be rendered to.
+fn render_target(size: Extent3d) -> Image {
 	let mut image = Image {
 		texture_descriptor: TextureDescriptor {
 			label: None,
@@ -70,7 +126,24 @@ fn setup(
 	// Fill image.data with zeroes
 	image.resize(size);
 
-	let image_handle = images.add(image);
+	image
+}
+
+fn setup(
+	mut commands: Commands,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut images: ResMut<Assets<Image>>,
+	windows: Res<Windows>,
+) {
+	let window_size = windows
+		.get_primary()
+		.map(|window| Vec2::new(window.width(), window.height()))
+		.unwrap_or_else(|| Vec2::new(WIDTH, HEIGHT));
+
+	let size = render_target_size(window_size.x, window_size.y);
+
+	// This is the texture that will be rendered to.
+	let image_handle = images.add(render_target(size));
 
 	// Add main camera rendering to the image
 	commands
@@ -83,81 +156,230 @@ fn setup(
 			..Default::default()
 		})
 		.insert(MainCamera)
+		.insert(CameraController::default())
+		.insert(UiCameraConfig { show_ui: false })
 		.insert(Name::new("MainCamera"));
 
-	// This specifies the layer used for the post processing camera, which will be attached to the post processing camera and 2d quad.
-	let post_processing_pass_layer = RenderLayers::layer((RenderLayers::TOTAL_LAYERS - 1) as u8);
-
-	let post_processing_pass_layer_resource = PostProcessingLayer(post_processing_pass_layer);
+	commands.insert_resource(PingPongImages([
+		images.add(render_target(size)),
+		images.add(render_target(size)),
+	]));
 
-	// This material has the texture that has been rendered.
-	let material_handle = post_processing_materials.add(DefaultMaterial {
-		source_image: image_handle.clone(),
+	commands.insert_resource(ScreenQuad(meshes.add(Mesh::from(shape::Quad::new(window_size)))));
+	commands.insert_resource(CameraRenderImage(image_handle));
+	commands.insert_resource(PassLayers {
+		next: (RenderLayers::TOTAL_LAYERS - 1) as u8,
 	});
+}
 
-	set_post_processing_effects(
-		&mut commands,
-		material_handle,
-		&mut meshes,
-		&post_processing_pass_layer_resource,
-	);
-
-	commands.insert_resource(post_processing_pass_layer_resource);
-	commands.insert_resource(CameraRenderImage(image_handle));
+/// Adds a pass to the chain, with its own quad and camera.
+/// The source image of the material gets replaced with the output of the previous pass.
+pub fn spawn_pass<M: Material2d>(
+	commands: &mut Commands,
+	layers: &mut PassLayers,
+	screen_quad: &ScreenQuad,
+	source_image: &CameraRenderImage,
+	material: Handle<M>,
+	pass: PostProcessingPass,
+	name: &str,
+) -> Entity {
+	let layer = RenderLayers::layer(layers.next);
+	layers.next = layers.next.checked_sub(1).expect("Too many post processing passes!");
 
-	// The post-processing pass camera.
-	commands
+	let camera = commands
 		.spawn_bundle(Camera2dBundle {
 			camera: Camera {
-				// renders after the first main camera which has default value: 0.
-				priority: 1,
+				is_active: false,
 				..default()
 			},
 			..Camera2dBundle::default()
 		})
-		.insert(post_processing_pass_layer)
-		.insert(Name::new("SecondaryCamera"));
-}
-
-fn set_post_processing_effects<M: Material2d>(
-	commands: &mut Commands,
-	material: Handle<M>,
-	meshes: &mut ResMut<Assets<Mesh>>,
-	post_processing_pass_layer: &PostProcessingLayer,
-) {
-	let quad_handle = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
-		WIDTH as f32,
-		HEIGHT as f32,
-	))));
+		.insert(layer)
+		.insert(UiCameraConfig { show_ui: false })
+		.insert(Name::new(format!("{}Camera", name)))
+		.id();
 
-	// Post processing 2d quad, with material using the render texture done by the main camera, with a custom shader.
-	let screen = commands
+	// Post processing 2d quad, with material using the render texture done by the previous pass, with a custom shader.
+	commands
 		.spawn_bundle(MaterialMesh2dBundle {
-			mesh: quad_handle.into(),
+			mesh: screen_quad.0.clone().into(),
 			material,
 			transform: Transform {
 				translation: Vec3::new(0.0, 0.0, 1.5),
 				..default()
 			},
+			visibility: Visibility { is_visible: false },
 			..default()
 		})
-		.insert(post_processing_pass_layer.0)
-		.insert(Name::new("Screen"))
-		.insert(Screen).id();
+		.insert(layer)
+		.insert(pass)
+		.insert(PassInput(source_image.0.clone()))
+		.insert(PassCamera(camera))
+		.insert(Name::new(name.to_string()))
+		.id()
+}
+
+fn spawn_global_passes(
+	mut commands: Commands,
+	mut layers: ResMut<PassLayers>,
+	screen_quad: Res<ScreenQuad>,
+	source_image: Res<CameraRenderImage>,
+	mut default_materials: ResMut<Assets<DefaultMaterial>>,
+	mut colorblind_materials: ResMut<Assets<ColorblindMaterial>>,
+) {
+	let colorblind = spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		colorblind_materials.add(ColorblindMaterial::new(source_image.0.clone())),
+		PostProcessingPass {
+			order: COLORBLIND_FILTER_ORDER,
+			enabled: false,
+		},
+		"ColorblindPass",
+	);
 
-	commands.insert_resource(ScreenRes(screen));
+	commands.entity(colorblind).insert(ColorblindPass);
+
+	// Always enabled, so something ends up on the window even when no effects are active
+	spawn_pass(
+		&mut commands,
+		&mut layers,
+		&screen_quad,
+		&source_image,
+		default_materials.add(DefaultMaterial {
+			source_image: source_image.0.clone(),
+		}),
+		PostProcessingPass {
+			order: OUTPUT_ORDER,
+			enabled: true,
+		},
+		"OutputPass",
+	);
 }
 
-pub fn update_post_processing_effects<M: Material2d>(
-	commands: &mut Commands,
-	screen: &Entity,
-	material: Handle<M>,
-	meshes: &mut ResMut<Assets<Mesh>>,
-	post_processing_pass_layer: &PostProcessingLayer,
+/// Chains the enabled passes together, from the main camera's image, through the ping-pong images, to the window.
+fn route_passes(
+	mut pass_query: Query<(&PostProcessingPass, &PassCamera, &mut PassInput, &mut Visibility)>,
+	mut camera_query: Query<(&mut Camera, &mut UiCameraConfig)>,
+	source_image: Res<CameraRenderImage>,
+	ping_pong: Res<PingPongImages>,
+) {
+	let mut passes: Vec<_> = pass_query.iter_mut().collect();
+	passes.sort_by_key(|(pass, ..)| (!pass.enabled, pass.order));
+
+	let enabled = passes.iter().filter(|(pass, ..)| pass.enabled).count();
+	let mut input = source_image.0.clone();
+
+	for (index, (pass, pass_camera, mut pass_input, mut visibility)) in passes.into_iter().enumerate() {
+		let (mut camera, mut ui_config) = match camera_query.get_mut(pass_camera.0) {
+			Ok(camera) => camera,
+			Err(_) => continue,
+		};
+
+		if !pass.enabled {
+			if camera.is_active {
+				camera.is_active = false;
+			}
+
+			if visibility.is_visible {
+				visibility.is_visible = false;
+			}
+
+			continue;
+		}
+
+		let output = ping_pong.0[index % 2].clone();
+		let is_last = index + 1 == enabled;
+		let target = if is_last {
+			RenderTarget::Window(WindowId::primary())
+		} else {
+			RenderTarget::Image(output.clone())
+		};
+
+		// Only touched when something changes, so the materials don't get rebuilt every frame
+		if camera.target != target || camera.priority != index as isize + 1 || !camera.is_active {
+			camera.target = target;
+			camera.priority = index as isize + 1;
+			camera.is_active = true;
+		}
+
+		// The UI only gets drawn once, over the finished image
+		if ui_config.show_ui != is_last {
+			ui_config.show_ui = is_last;
+		}
+
+		if !visibility.is_visible {
+			visibility.is_visible = true;
+		}
+
+		if pass_input.0 != input {
+			pass_input.0 = input;
+		}
+
+		input = output;
+	}
+}
+
+fn update_pass_source<M: PostProcessingMaterial>(
+	pass_query: Query<(&PassInput, &Handle<M>), Changed<PassInput>>,
+	mut materials: ResMut<Assets<M>>,
+) {
+	for (input, material) in pass_query.iter() {
+		if let Some(material) = materials.get_mut(material) {
+			*material.source_image() = input.0.clone();
+		}
+	}
+}
+
+fn update_colorblind_filter(
+	mut pass_query: Query<(&mut PostProcessingPass, &Handle<ColorblindMaterial>), With<ColorblindPass>>,
+	mut materials: ResMut<Assets<ColorblindMaterial>>,
+	settings: Res<Settings>,
 ) {
-	commands.entity(*screen).despawn_recursive();
+	if !settings.is_changed() {
+		return;
+	}
 
-	set_post_processing_effects(commands, material, meshes, post_processing_pass_layer);
+	for (mut pass, material) in pass_query.iter_mut() {
+		pass.enabled = settings.colorblind_filter != ColorblindFilter::None;
+
+		if let Some(material) = materials.get_mut(material) {
+			material.mode = settings.colorblind_filter as u32;
+		}
+	}
+}
+
+/// Keeps the render targets and the screen quad the same size as the window.
+fn resize_render_target(
+	mut resize_events: EventReader<WindowResized>,
+	mut images: ResMut<Assets<Image>>,
+	mut meshes: ResMut<Assets<Mesh>>,
+	source_image: Res<CameraRenderImage>,
+	ping_pong: Res<PingPongImages>,
+	screen_quad: Res<ScreenQuad>,
+) {
+	for event in resize_events.iter() {
+		if event.id != WindowId::primary() {
+			continue;
+		}
+
+		// The window reports a size of zero when it gets minimized
+		if event.width < 1.0 || event.height < 1.0 {
+			continue;
+		}
+
+		for image in std::iter::once(&source_image.0).chain(ping_pong.0.iter()) {
+			if let Some(image) = images.get_mut(image) {
+				image.resize(render_target_size(event.width, event.height));
+			}
+		}
+
+		if let Some(quad) = meshes.get_mut(&screen_quad.0) {
+			*quad = Mesh::from(shape::Quad::new(Vec2::new(event.width, event.height)));
+		}
+	}
 }
 
 /// Default post processing material
@@ -175,3 +397,79 @@ impl Material2d for DefaultMaterial {
 		"shaders/default.wgsl".into()
 	}
 }
+
+impl PostProcessingMaterial for DefaultMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorblindFilter {
+	None = 0,
+	Protanopia = 1,
+	Deuteranopia = 2,
+	Tritanopia = 3,
+}
+
+impl ColorblindFilter {
+	pub fn next(self) -> Self {
+		match self {
+			ColorblindFilter::None => ColorblindFilter::Protanopia,
+			ColorblindFilter::Protanopia => ColorblindFilter::Deuteranopia,
+			ColorblindFilter::Deuteranopia => ColorblindFilter::Tritanopia,
+			ColorblindFilter::Tritanopia => ColorblindFilter::None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			ColorblindFilter::None => "Off",
+			ColorblindFilter::Protanopia => "Protanopia",
+			ColorblindFilter::Deuteranopia => "Deuteranopia",
+			ColorblindFilter::Tritanopia => "Tritanopia",
+		}
+	}
+}
+
+/// Shifts the colors that are hard to tell apart with the given kind of colour blindness.
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "9b5e7c1a-3f2d-4e8b-a6c0-58d4f1e2b7a9"]
+pub struct ColorblindMaterial {
+	#[texture(0)]
+	#[sampler(1)]
+	pub source_image: Handle<Image>,
+	/// A `ColorblindFilter`
+	#[uniform(2)]
+	pub mode: u32,
+	#[uniform(2)]
+	pub _wasm_padding_0: u32,
+	#[uniform(2)]
+	pub _wasm_padding_1: u32,
+	#[uniform(2)]
+	pub _wasm_padding_2: u32,
+}
+
+impl ColorblindMaterial {
+	pub fn new(source_image: Handle<Image>) -> Self {
+		Self {
+			source_image,
+			mode: 0,
+			_wasm_padding_0: 0,
+			_wasm_padding_1: 0,
+			_wasm_padding_2: 0,
+		}
+	}
+}
+
+impl Material2d for ColorblindMaterial {
+	fn fragment_shader() -> ShaderRef {
+		"shaders/colorblind.wgsl".into()
+	}
+}
+
+impl PostProcessingMaterial for ColorblindMaterial {
+	fn source_image(&mut self) -> &mut Handle<Image> {
+		&mut self.source_image
+	}
+}
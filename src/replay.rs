@@ -0,0 +1,323 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Viewport;
+use crate::checkpoint::Checkpoints;
+use crate::difficulty::Difficulty;
+use crate::player_input::PlayerInput;
+use crate::rng::GameRng;
+use crate::save::PendingLoad;
+use crate::settings::Settings;
+use crate::stats::Stats;
+use crate::storage::Storage;
+use crate::time::TimeCounter;
+use crate::GameState;
+
+const REPLAY_KEY: &str = "replay";
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ReplayFrame {
+	input: PlayerInput,
+	delta: f32,
+	real_delta: f32,
+}
+
+/// How the recorded run ended, a replay that ends differently went out of sync.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+struct ReplayOutcome {
+	elapsed: f32,
+	enemies_killed: u16,
+	shots_fired: u16,
+	damage_taken: f32,
+}
+
+impl ReplayOutcome {
+	fn of(stats: &Stats) -> Self {
+		Self {
+			elapsed: stats.timer.elapsed_secs(),
+			enemies_killed: stats.enemies_killed,
+			shots_fired: stats.shot_fired,
+			damage_taken: stats.damage_taken,
+		}
+	}
+}
+
+/// A run from start to finish, played back from its seed and the input of every frame.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+	seed: u64,
+	difficulty: Difficulty,
+	/// The camera and what's on the screen depend on it, so a replay only matches on a viewport of the same size
+	viewport_size: Vec2,
+	frames: Vec<ReplayFrame>,
+	outcome: Option<ReplayOutcome>,
+}
+
+impl Replay {
+	fn load(storage: &Storage) -> Option<Self> {
+		let replay = storage.read(REPLAY_KEY)?;
+
+		ron::from_str(&replay)
+			.map_err(|error| warn!("Failed to read the replay: {}", error))
+			.ok()
+	}
+
+	fn write(&self, storage: &Storage) {
+		let replay = match ron::to_string(self) {
+			Ok(replay) => replay,
+			Err(error) => {
+				error!("Failed to serialize the replay: {}", error);
+				return;
+			}
+		};
+
+		if let Err(error) = storage.write(REPLAY_KEY, &replay) {
+			error!("Failed to write the replay: {}", error);
+		}
+	}
+}
+
+/// How a watched replay ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayResult {
+	/// It ended the same way the recorded run did
+	InSync,
+	OutOfSync,
+	/// It was stopped before the end, so there's no telling
+	Stopped,
+}
+
+struct Playback {
+	replay: Replay,
+	next_frame: usize,
+	/// The difficulty from the settings, to go back to once the replay is over
+	difficulty: Difficulty,
+}
+
+/// Records every run that can be replayed and plays them back.
+///
+/// A run is only recorded if it's played from the start without respawning,
+/// continued saves and checkpoints would need the state of the level at that point.
+#[derive(Default)]
+pub struct Replays {
+	recording: Option<Replay>,
+	/// The run that was won last, if it can be replayed
+	last: Option<Replay>,
+	playback: Option<Playback>,
+	watched: bool,
+	result: Option<ReplayResult>,
+}
+
+impl FromWorld for Replays {
+	fn from_world(world: &mut World) -> Self {
+		let storage = world.get_resource_or_insert_with(Storage::default);
+
+		Self {
+			last: Replay::load(&storage),
+			..Default::default()
+		}
+	}
+}
+
+impl Replays {
+	pub fn can_watch(&self) -> bool {
+		self.last.is_some()
+	}
+
+	/// Starts playing back the last run once the game state changes to `Game`.
+	pub fn watch(&mut self, settings: &mut Settings, rng: &mut GameRng, viewport: &Viewport) {
+		let replay = match &self.last {
+			Some(replay) => replay.clone(),
+			None => return,
+		};
+
+		if viewport.size != replay.viewport_size {
+			warn!("The replay was recorded on a window of a different size, it might go out of sync");
+		}
+
+		rng.set_next_seed(replay.seed);
+
+		let difficulty = std::mem::replace(&mut settings.difficulty, replay.difficulty);
+
+		self.result = None;
+		self.playback = Some(Playback {
+			replay,
+			next_frame: 0,
+			difficulty,
+		});
+	}
+
+	pub fn is_playing(&self) -> bool {
+		self.playback.is_some()
+	}
+
+	/// True if the run that just ended was a replay, so it doesn't count for the records.
+	pub fn watched(&self) -> bool {
+		self.watched
+	}
+
+	/// How the replay that was watched last ended.
+	pub fn result(&self) -> Option<ReplayResult> {
+		self.result
+	}
+
+	/// # Returns
+	/// False once the replay ran out of frames.
+	fn play_frame(&mut self, input: &mut PlayerInput, time: &mut TimeCounter) -> bool {
+		let playback = match &mut self.playback {
+			Some(playback) => playback,
+			None => return true,
+		};
+
+		let frame = match playback.replay.frames.get(playback.next_frame) {
+			Some(frame) => frame,
+			None => return false,
+		};
+
+		*input = frame.input.clone();
+		time.replay_frame(frame.delta, frame.real_delta);
+		playback.next_frame += 1;
+
+		true
+	}
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<Replays>()
+			.add_system_to_stage(
+				CoreStage::PreUpdate,
+				play_replay.after("sample_input").after("update_time"),
+			)
+			.add_system_set(
+				SystemSet::on_enter(GameState::Game).with_system(
+					begin_run.after("seed_run").before("begin_attempt"),
+				),
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game)
+					.with_system(record_frame)
+					.with_system(stop_replay_on_escape),
+			)
+			.add_system_set(SystemSet::on_exit(GameState::Game).with_system(end_replay))
+			.add_system_set(
+				SystemSet::on_enter(GameState::Win).with_system(finish_recording.before("record_run")),
+			);
+	}
+}
+
+/// Starts recording the run, or plays the first frame of the replay.
+/// The rest of the frames are played before every other frame of the game, from `play_replay`.
+fn begin_run(
+	mut replays: ResMut<Replays>,
+	mut input: ResMut<PlayerInput>,
+	mut time: ResMut<TimeCounter>,
+	rng: Res<GameRng>,
+	settings: Res<Settings>,
+	checkpoints: Res<Checkpoints>,
+	pending_load: Res<PendingLoad>,
+	viewport: Res<Viewport>,
+) {
+	replays.watched = false;
+
+	if replays.is_playing() {
+		replays.play_frame(input.as_mut(), time.as_mut());
+		replays.recording = None;
+		return;
+	}
+
+	if checkpoints.is_respawning() || pending_load.0.is_some() {
+		replays.recording = None;
+		return;
+	}
+
+	replays.recording = Some(Replay {
+		seed: rng.seed(),
+		difficulty: settings.difficulty,
+		viewport_size: viewport.size,
+		frames: Vec::new(),
+		outcome: None,
+	});
+}
+
+fn play_replay(
+	mut replays: ResMut<Replays>,
+	mut input: ResMut<PlayerInput>,
+	mut time: ResMut<TimeCounter>,
+	mut state: ResMut<State<GameState>>,
+) {
+	// The first frame was already played when the run began
+	if state.current() != &GameState::Game {
+		return;
+	}
+
+	if !replays.play_frame(input.as_mut(), time.as_mut()) {
+		warn!("The replay ended before the run did");
+		*input = PlayerInput::default();
+
+		if state.set(GameState::MainMenu).is_err() {}
+	}
+}
+
+fn record_frame(mut replays: ResMut<Replays>, input: Res<PlayerInput>, time: Res<TimeCounter>) {
+	if let Some(recording) = &mut replays.recording {
+		recording.frames.push(ReplayFrame {
+			input: input.clone(),
+			delta: time.delta_seconds(),
+			real_delta: time.real_delta_seconds(),
+		});
+	}
+}
+
+fn stop_replay_on_escape(
+	keyboard: Res<Input<KeyCode>>,
+	replays: Res<Replays>,
+	mut state: ResMut<State<GameState>>,
+) {
+	#[allow(clippy::collapsible_if)]
+	if replays.is_playing() && keyboard.just_pressed(KeyCode::Escape) {
+		if state.set(GameState::MainMenu).is_err() {}
+	}
+}
+
+fn end_replay(mut replays: ResMut<Replays>, mut settings: ResMut<Settings>, stats: Res<Stats>) {
+	let playback = match replays.playback.take() {
+		Some(playback) => playback,
+		None => return,
+	};
+
+	settings.difficulty = playback.difficulty;
+	replays.watched = true;
+
+	// Only a replay that was watched to the end can tell if it went out of sync
+	if playback.next_frame < playback.replay.frames.len() {
+		replays.result = Some(ReplayResult::Stopped);
+		return;
+	}
+
+	let outcome = ReplayOutcome::of(&stats);
+
+	if playback.replay.outcome.as_ref() == Some(&outcome) {
+		replays.result = Some(ReplayResult::InSync);
+	} else {
+		warn!(
+			"The replay went out of sync, expected {:?} but got {:?}",
+			playback.replay.outcome, outcome
+		);
+		replays.result = Some(ReplayResult::OutOfSync);
+	}
+}
+
+fn finish_recording(mut replays: ResMut<Replays>, stats: Res<Stats>, storage: Res<Storage>) {
+	if replays.watched {
+		return;
+	}
+
+	replays.last = replays.recording.take().map(|mut replay| {
+		replay.outcome = Some(ReplayOutcome::of(&stats));
+		replay.write(&storage);
+		replay
+	});
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::checkpoint::Checkpoints;
+use crate::GameState;
+
+/// Mixed into the seed of the cosmetic stream, so it doesn't repeat the gameplay one
+const COSMETIC_STREAM: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// All the randomness of a run, reseeded whenever a run starts so it can be reproduced from its seed.
+///
+/// Everything that changes how the run plays out draws from the gameplay stream, and everything
+/// that's only for show draws from the cosmetic one, so adding a new effect doesn't change the run.
+pub struct GameRng {
+	seed: u64,
+	next_seed: Option<u64>,
+	gameplay: StdRng,
+	cosmetic: StdRng,
+}
+
+impl GameRng {
+	fn new(seed: u64) -> Self {
+		Self {
+			seed,
+			next_seed: None,
+			gameplay: StdRng::seed_from_u64(seed),
+			cosmetic: StdRng::seed_from_u64(seed ^ COSMETIC_STREAM),
+		}
+	}
+
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	/// The next run starts with this seed instead of a random one, for replays and shared seeds.
+	pub fn set_next_seed(&mut self, seed: u64) {
+		self.next_seed = Some(seed);
+	}
+
+	pub fn reseed(&mut self, seed: u64) {
+		*self = Self {
+			next_seed: self.next_seed,
+			..Self::new(seed)
+		};
+	}
+
+	pub fn gameplay(&mut self) -> &mut StdRng {
+		&mut self.gameplay
+	}
+
+	pub fn cosmetic(&mut self) -> &mut StdRng {
+		&mut self.cosmetic
+	}
+}
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(GameRng::new(rand::random()))
+			.add_system_set(SystemSet::on_enter(GameState::Game).with_system(seed_run.label("seed_run")));
+	}
+}
+
+fn seed_run(mut rng: ResMut<GameRng>, checkpoints: Res<Checkpoints>) {
+	// Respawning keeps going with the same run, and the same generators
+	if checkpoints.is_respawning() {
+		return;
+	}
+
+	let seed = rng.next_seed.take().unwrap_or_else(rand::random);
+
+	rng.reseed(seed);
+
+	info!("Run seed: {}", seed);
+}
@@ -0,0 +1,249 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::{CheckpointSnapshot, Checkpoints};
+use crate::difficulty::{Difficulty, RunDifficulty};
+use crate::enemy::{Enemy, EnemyAiState};
+use crate::pickup::Pickup;
+use crate::rng::GameRng;
+use crate::player::{ActiveEffect, Effects, Player};
+use crate::stats::Stats;
+use crate::storage::Storage;
+use crate::tilemap::TilePosition;
+use crate::unit::{Health, Inventory};
+use crate::GameState;
+
+const SAVE_KEY: &str = "run";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(PendingLoad(None))
+			.add_event::<SaveRunEvent>()
+			.add_system_set(
+				SystemSet::on_update(GameState::Paused)
+					.with_system(write_save.after("save_and_quit_button")),
+			)
+			.add_system_set(
+				SystemSet::on_update(GameState::Game).with_system(
+					restore_save
+						.label("restore_save")
+						.before("player_movement")
+						.before("update_enemy_ai"),
+				),
+			);
+	}
+}
+
+/// Sent to save the current run to the disk.
+pub struct SaveRunEvent;
+
+/// A save loaded from the disk, waiting for the level to be spawned so it can be applied to it.
+#[derive(Deref, DerefMut)]
+pub struct PendingLoad(pub Option<SaveGame>);
+
+#[derive(Serialize, Deserialize)]
+struct SavedPlayer {
+	translation: Vec3,
+	rotation: Quat,
+	health: Health,
+	inventory: Inventory,
+	effects: Vec<ActiveEffect>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedEnemy {
+	tile: IVec2,
+	translation: Vec3,
+	rotation: Quat,
+	ai_state: EnemyAiState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedStats {
+	elapsed: f32,
+	enemies_killed: u16,
+	items_used: HashMap<String, u16>,
+	items_collected: HashMap<String, u16>,
+	recipes_crafted: HashMap<String, u16>,
+	damage_taken: f32,
+	shot_fired: u16,
+	deaths: u16,
+}
+
+/// The state of a run in progress.
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+	player: SavedPlayer,
+	enemies: Vec<SavedEnemy>,
+	pickups: Vec<IVec2>,
+	stats: SavedStats,
+	/// A run is continued on the difficulty it was started on
+	difficulty: Difficulty,
+	/// Continuing reseeds the run, the streams can't pick up where they were
+	seed: u64,
+	/// Where the player respawns after dying in the continued run
+	checkpoint: Option<CheckpointSnapshot>,
+}
+
+impl SaveGame {
+	/// A save that can't be read gets deleted, so the main menu doesn't offer to continue it.
+	fn read(storage: &Storage) -> Option<Self> {
+		let save = storage.read(SAVE_KEY)?;
+
+		match ron::from_str(&save) {
+			Ok(save) => Some(save),
+			Err(error) => {
+				warn!("Failed to read the save, deleting it: {}", error);
+				storage.remove(SAVE_KEY);
+				None
+			}
+		}
+	}
+
+	pub fn exists(storage: &Storage) -> bool {
+		Self::read(storage).is_some()
+	}
+
+	/// Reads the save and removes it from the disk, so the same run can't be continued twice.
+	pub fn take(storage: &Storage) -> Option<Self> {
+		let save = Self::read(storage)?;
+		storage.remove(SAVE_KEY);
+
+		Some(save)
+	}
+
+	pub fn difficulty(&self) -> Difficulty {
+		self.difficulty
+	}
+
+	fn write(&self, storage: &Storage) {
+		let save = match ron::to_string(self) {
+			Ok(save) => save,
+			Err(error) => {
+				error!("Failed to serialize the save: {}", error);
+				return;
+			}
+		};
+
+		if let Err(error) = storage.write(SAVE_KEY, &save) {
+			error!("Failed to write the save: {}", error);
+		}
+	}
+}
+
+fn write_save(
+	mut save_events: EventReader<SaveRunEvent>,
+	player_query: Query<(&Transform, &Health, &Inventory, &Effects), With<Player>>,
+	enemy_query: Query<(&Transform, &Enemy, &TilePosition), Without<Player>>,
+	pickup_query: Query<&TilePosition, With<Pickup>>,
+	stats: Res<Stats>,
+	checkpoints: Res<Checkpoints>,
+	run_difficulty: Res<RunDifficulty>,
+	rng: Res<GameRng>,
+	storage: Res<Storage>,
+) {
+	if save_events.iter().count() == 0 {
+		return;
+	}
+
+	let (player_transform, health, inventory, effects) = player_query.single();
+
+	let save = SaveGame {
+		player: SavedPlayer {
+			translation: player_transform.translation,
+			rotation: player_transform.rotation,
+			health: health.clone(),
+			inventory: inventory.clone(),
+			effects: effects.active().to_vec(),
+		},
+		enemies: enemy_query
+			.iter()
+			.map(|(transform, enemy, tile)| SavedEnemy {
+				tile: tile.0,
+				translation: transform.translation,
+				rotation: transform.rotation,
+				ai_state: enemy.ai_state().clone(),
+			})
+			.collect(),
+		pickups: pickup_query.iter().map(|tile| tile.0).collect(),
+		stats: SavedStats {
+			elapsed: stats.timer.elapsed_secs(),
+			enemies_killed: stats.enemies_killed,
+			items_used: stats.items_used.clone(),
+			items_collected: stats.items_collected.clone(),
+			recipes_crafted: stats.recipes_crafted.clone(),
+			damage_taken: stats.damage_taken,
+			shot_fired: stats.shot_fired,
+			deaths: stats.deaths,
+		},
+		difficulty: **run_difficulty,
+		seed: rng.seed(),
+		checkpoint: checkpoints.last().cloned(),
+	};
+
+	save.write(&storage);
+}
+
+fn restore_save(
+	mut commands: Commands,
+	mut pending_load: ResMut<PendingLoad>,
+	mut player_query: Query<(&mut Transform, &mut Health, &mut Inventory, &mut Effects), With<Player>>,
+	mut enemy_query: Query<(Entity, &mut Transform, &mut Enemy, &TilePosition), Without<Player>>,
+	pickup_query: Query<(Entity, &TilePosition), With<Pickup>>,
+	mut stats: ResMut<Stats>,
+	mut checkpoints: ResMut<Checkpoints>,
+	mut rng: ResMut<GameRng>,
+) {
+	let save = match pending_load.take() {
+		Some(save) => save,
+		None => return,
+	};
+
+	let (mut player_transform, mut health, mut inventory, mut effects) = player_query.single_mut();
+
+	player_transform.translation = save.player.translation;
+	player_transform.rotation = save.player.rotation;
+	*health = save.player.health;
+	*inventory = save.player.inventory;
+	// The saved health already includes everything the effects did to it
+	effects.restore(save.player.effects);
+
+	for (enemy_entity, mut transform, mut enemy, tile) in enemy_query.iter_mut() {
+		match save.enemies.iter().find(|saved| saved.tile == tile.0) {
+			Some(saved) => {
+				transform.translation = saved.translation;
+				transform.rotation = saved.rotation;
+				enemy.set_ai_state(saved.ai_state.clone());
+			}
+			None => {
+				checkpoints.enemy_killed(tile.0);
+				commands.entity(enemy_entity).despawn_recursive();
+			}
+		}
+	}
+
+	for (pickup, tile) in pickup_query.iter() {
+		if !save.pickups.contains(&tile.0) {
+			checkpoints.pickup_collected(tile.0);
+			commands.entity(pickup).despawn_recursive();
+		}
+	}
+
+	stats.timer.set_elapsed(Duration::from_secs_f32(save.stats.elapsed));
+	stats.enemies_killed = save.stats.enemies_killed;
+	stats.items_used = save.stats.items_used;
+	stats.items_collected = save.stats.items_collected;
+	stats.recipes_crafted = save.stats.recipes_crafted;
+	stats.damage_taken = save.stats.damage_taken;
+	stats.shot_fired = save.stats.shot_fired;
+	stats.deaths = save.stats.deaths;
+
+	checkpoints.restore(save.checkpoint);
+
+	rng.reseed(save.seed);
+}
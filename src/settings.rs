@@ -2,7 +2,9 @@ use bevy::prelude::*;
 
 use crate::{
 	button::ColoredButton,
+	difficulty::Difficulty,
 	fonts::{PaintFont, RobotoFont},
+	post_processing::ColorblindFilter,
 	GameState,
 };
 
@@ -18,6 +20,9 @@ struct MusicVolumeAmount;
 #[derive(Component)]
 struct SfxVolumeAmount;
 
+#[derive(Component)]
+struct ShakeAmount;
+
 #[derive(Component)]
 struct SubSfxButton;
 
@@ -30,11 +35,28 @@ struct SubMusicButton;
 #[derive(Component)]
 struct AddMusicButton;
 
+#[derive(Component)]
+struct SubShakeButton;
+
+#[derive(Component)]
+struct AddShakeButton;
+
+#[derive(Component)]
+struct ColorblindFilterButton;
+
+#[derive(Component)]
+struct ColorblindFilterName;
+
 pub struct SettingsPlugin;
 
 pub struct Settings {
 	pub sfx_volume: f64,
 	pub music_volume: f64,
+	/// Scales the screen shake and hit-stop, zero turns them off
+	pub screen_shake: f32,
+	pub colorblind_filter: ColorblindFilter,
+	/// Picked in the main menu
+	pub difficulty: Difficulty,
 }
 
 impl Plugin for SettingsPlugin {
@@ -42,6 +64,9 @@ impl Plugin for SettingsPlugin {
 		app.insert_resource(Settings {
 			sfx_volume: 1.0,
 			music_volume: 1.0,
+			screen_shake: 1.0,
+			colorblind_filter: ColorblindFilter::None,
+			difficulty: Difficulty::default(),
 		})
 		.add_system_set(SystemSet::on_enter(GameState::Settings).with_system(load_ui))
 		.add_system_set(SystemSet::on_update(GameState::Settings)
@@ -51,6 +76,9 @@ impl Plugin for SettingsPlugin {
 			.with_system(add_music_button)
 			.with_system(sub_sfx_button)
 			.with_system(add_sfx_button)
+			.with_system(sub_shake_button)
+			.with_system(add_shake_button)
+			.with_system(colorblind_filter_button)
 		)
 		.add_system_set(SystemSet::on_exit(GameState::Settings).with_system(drop_ui));
 	}
@@ -61,7 +89,11 @@ fn load_ui(
 	roboto_font: Res<RobotoFont>,
 	paint_font: Res<PaintFont>,
 	settings: Res<Settings>,
+	state: Res<State<GameState>>,
 ) {
+	// When opened from the pause menu, the button takes you back there instead
+	let back_label = if state.inactives().is_empty() { "Main Menu" } else { "Back" };
+
 	commands
 		.spawn_bundle(NodeBundle {
 			style: Style {
@@ -95,7 +127,7 @@ fn load_ui(
 				.insert(ColoredButton::default())
 				.with_children(|parent| {
 					parent.spawn_bundle(TextBundle::from_section(
-						"Main Menu",
+						back_label,
 						TextStyle {
 							font: roboto_font.0.clone(),
 							font_size: 32.0,
@@ -336,6 +368,178 @@ fn load_ui(
 								});
 						});
 				});
+
+			parent
+				.spawn_bundle(NodeBundle {
+					style: Style {
+						size: Size::new(Val::Percent(50.0), Val::Px(50.0)),
+						justify_content: JustifyContent::SpaceBetween,
+						..Default::default()
+					},
+					color: Color::NONE.into(),
+					..Default::default()
+				})
+				.insert(Name::new("ShakeContainer"))
+				.with_children(|parent| {
+					parent
+						.spawn_bundle(
+							TextBundle::from_section(
+								format!("Shake: "),
+								TextStyle {
+									font: paint_font.0.clone(),
+									font_size: 32.0,
+									color: Color::WHITE,
+								},
+							)
+							.with_style(Style {
+								margin: UiRect::all(Val::Px(5.0)),
+								..default()
+							}),
+						)
+						.insert(Name::new("ShakeLabel"));
+
+					parent
+						.spawn_bundle(NodeBundle {
+							style: Style {
+								size: Size::new(Val::Px(200.0), Val::Percent(100.0)),
+								..Default::default()
+							},
+							color: Color::NONE.into(),
+							..Default::default()
+						})
+						.insert(Name::new("ShakeButtonsContainer"))
+						.with_children(|parent| {
+
+							parent
+								.spawn_bundle(ButtonBundle {
+									style: Style {
+										size: Size::new(Val::Px(60.0), Val::Percent(100.0)),
+										justify_content: JustifyContent::Center,
+										align_items: AlignItems::Center,
+										..Default::default()
+									},
+									button: Button,
+									color: Color::RED.into(),
+									..Default::default()
+								})
+								.insert(Name::new("SubShakeButton"))
+								.insert(SubShakeButton)
+								.insert(ColoredButton::default())
+								.with_children(|parent| {
+									parent.spawn_bundle(TextBundle::from_section(
+										"-",
+										TextStyle {
+											font: roboto_font.0.clone(),
+											font_size: 32.0,
+											color: Color::BLACK,
+										},
+									));
+								});
+
+							parent
+								.spawn_bundle(
+									TextBundle::from_section(
+										format!("{:3.0}", settings.screen_shake * 100.0),
+										TextStyle {
+											font: paint_font.0.clone(),
+											font_size: 32.0,
+											color: Color::WHITE,
+										},
+									)
+									.with_style(Style {
+										size: Size::new(Val::Px(100.0), Val::Percent(100.0)),
+										margin: UiRect::all(Val::Px(5.0)),
+										..default()
+									}),
+								)
+								.insert(Name::new("ShakeAmount"))
+								.insert(ShakeAmount);
+
+							parent
+								.spawn_bundle(ButtonBundle {
+									style: Style {
+										size: Size::new(Val::Px(60.0), Val::Percent(100.0)),
+										justify_content: JustifyContent::Center,
+										align_items: AlignItems::Center,
+										..Default::default()
+									},
+									button: Button,
+									color: Color::RED.into(),
+									..Default::default()
+								})
+								.insert(Name::new("AddShakeButton"))
+								.insert(AddShakeButton)
+								.insert(ColoredButton::default())
+								.with_children(|parent| {
+									parent.spawn_bundle(TextBundle::from_section(
+										"+",
+										TextStyle {
+											font: roboto_font.0.clone(),
+											font_size: 32.0,
+											color: Color::BLACK,
+										},
+									));
+								});
+						});
+				});
+
+			parent
+				.spawn_bundle(NodeBundle {
+					style: Style {
+						size: Size::new(Val::Percent(50.0), Val::Px(50.0)),
+						justify_content: JustifyContent::SpaceBetween,
+						..Default::default()
+					},
+					color: Color::NONE.into(),
+					..Default::default()
+				})
+				.insert(Name::new("ColorblindFilterContainer"))
+				.with_children(|parent| {
+					parent
+						.spawn_bundle(
+							TextBundle::from_section(
+								format!("Colors: "),
+								TextStyle {
+									font: paint_font.0.clone(),
+									font_size: 32.0,
+									color: Color::WHITE,
+								},
+							)
+							.with_style(Style {
+								margin: UiRect::all(Val::Px(5.0)),
+								..default()
+							}),
+						)
+						.insert(Name::new("ColorblindFilterLabel"));
+
+					parent
+						.spawn_bundle(ButtonBundle {
+							style: Style {
+								size: Size::new(Val::Px(200.0), Val::Percent(100.0)),
+								justify_content: JustifyContent::Center,
+								align_items: AlignItems::Center,
+								..Default::default()
+							},
+							button: Button,
+							color: Color::RED.into(),
+							..Default::default()
+						})
+						.insert(Name::new("ColorblindFilterButton"))
+						.insert(ColorblindFilterButton)
+						.insert(ColoredButton::default())
+						.with_children(|parent| {
+							parent
+								.spawn_bundle(TextBundle::from_section(
+									settings.colorblind_filter.name(),
+									TextStyle {
+										font: roboto_font.0.clone(),
+										font_size: 24.0,
+										color: Color::BLACK,
+									},
+								))
+								.insert(ColorblindFilterName);
+						});
+				});
 		});
 }
 
@@ -346,6 +550,7 @@ fn update_ui(
         (
             With<MusicVolumeAmount>,
             Without<SfxVolumeAmount>,
+            Without<ShakeAmount>,
         ),
     >,
 	mut sfx_volume_query: Query<
@@ -353,6 +558,15 @@ fn update_ui(
         (
             With<SfxVolumeAmount>,
             Without<MusicVolumeAmount>,
+            Without<ShakeAmount>,
+        ),
+    >,
+	mut shake_query: Query<
+        &mut Text,
+        (
+            With<ShakeAmount>,
+            Without<MusicVolumeAmount>,
+            Without<SfxVolumeAmount>,
         ),
     >,
 ) {
@@ -361,6 +575,9 @@ fn update_ui(
 
 	let mut sfx_volume = sfx_volume_query.single_mut();
 	sfx_volume.sections[0].value = format!("{:3.0}", settings.sfx_volume * 100.0);
+
+	let mut shake = shake_query.single_mut();
+	shake.sections[0].value = format!("{:3.0}", settings.screen_shake * 100.0);
 }
 
 fn drop_ui(mut commands: Commands, ui: Query<Entity, With<SettingsUi>>) {
@@ -414,15 +631,57 @@ fn add_sfx_button(
 	
 }
 
+fn sub_shake_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<SubShakeButton>)>,
+	mut settings: ResMut<Settings>
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			settings.screen_shake = (settings.screen_shake - 0.1).clamp(0.0, 1.0);
+		}
+	}
+}
+
+fn add_shake_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<AddShakeButton>)>,
+	mut settings: ResMut<Settings>
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			settings.screen_shake = (settings.screen_shake + 0.1).clamp(0.0, 1.0);
+		}
+	}
+}
+
+fn colorblind_filter_button(
+	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<ColorblindFilterButton>)>,
+	mut name_query: Query<&mut Text, With<ColorblindFilterName>>,
+	mut settings: ResMut<Settings>
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			settings.colorblind_filter = settings.colorblind_filter.next();
+
+			for mut name in &mut name_query {
+				name.sections[0].value = settings.colorblind_filter.name().to_string();
+			}
+		}
+	}
+}
+
 fn main_menu_button(
 	mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<MainMenuButton>)>,
 	mut state: ResMut<State<GameState>>,
 ) {
 	for interaction in &mut interaction_query {
 		if *interaction == Interaction::Clicked {
-			state
-				.set(GameState::MainMenu)
-				.expect("Failed to change state!");
+			if state.inactives().is_empty() {
+				state
+					.set(GameState::MainMenu)
+					.expect("Failed to change state!");
+			} else {
+				state.pop().expect("Failed to change state!");
+			}
 		}
 	}
 }
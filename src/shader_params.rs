@@ -0,0 +1,65 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, render::render_resource::ShaderType};
+use bevy_inspector_egui::Inspectable;
+
+/// The uniform the drug and win shaders get, has to match `ShaderParams` in their `.wgsl` files.
+#[derive(Clone, Copy, PartialEq, ShaderType, Inspectable)]
+pub struct ShaderParams {
+	/// In milliseconds, set by the game every frame
+	#[inspectable(ignore)]
+	pub time: f32,
+	/// How much of the effect gets mixed into the image, zero leaves it untouched
+	#[inspectable(min = 0.0, max = 1.0, speed = 0.01)]
+	pub intensity: f32,
+	/// In radians
+	#[inspectable(min = 0.0, max = TAU, speed = 0.01)]
+	pub hue_shift: f32,
+	#[inspectable(min = 0.0, max = 5.0, speed = 0.01)]
+	pub distortion: f32,
+}
+
+impl ShaderParams {
+	pub fn new(intensity: f32, hue_shift: f32, distortion: f32) -> Self {
+		Self {
+			time: 0.0,
+			intensity,
+			hue_shift,
+			distortion,
+		}
+	}
+
+	pub fn at(self, seconds: f32) -> Self {
+		Self {
+			time: (seconds * 1000.0).floor(),
+			..self
+		}
+	}
+}
+
+/// The parameters of every shader, copied into their materials every frame so they can be tweaked in the inspector.
+#[derive(Inspectable)]
+pub struct ShaderTuning {
+	pub small_powerup: ShaderParams,
+	pub big_powerup: ShaderParams,
+	pub win: ShaderParams,
+}
+
+impl Default for ShaderTuning {
+	fn default() -> Self {
+		Self {
+			small_powerup: ShaderParams::new(1.0, 3.62, 1.0),
+			big_powerup: ShaderParams::new(1.0, 0.0, 0.5),
+			// The win screen is just black for now
+			win: ShaderParams::new(0.0, 0.0, 0.0),
+		}
+	}
+}
+
+pub struct ShaderParamsPlugin;
+
+impl Plugin for ShaderParamsPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<ShaderTuning>();
+	}
+}
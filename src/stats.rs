@@ -1,6 +1,7 @@
-use bevy::{prelude::*, time::Stopwatch};
+use bevy::{prelude::*, time::Stopwatch, utils::HashMap};
+use serde::{Deserialize, Serialize};
 
-use crate::{GameState, fonts::{PaintFont, RobotoFont}, button::ColoredButton};
+use crate::{GameState, fonts::{PaintFont, RobotoFont}, button::ColoredButton, checkpoint::Checkpoints, crafting::RecipeRegistry, highscores::{HighScores, NewRecords, RECORD_COLOR}, camera::Viewport, difficulty::RunDifficulty, replay::{ReplayResult, Replays}, rng::GameRng, settings::Settings, tilemap::LEVEL_ID, time::TimeCounter};
 
 #[derive(Component)]
 struct StatsUi;
@@ -8,19 +9,68 @@ struct StatsUi;
 
 struct MainMenuButton;
 
+#[derive(Component)]
+struct WatchReplayButton;
+
 pub struct StatsPlugin;
 
 #[derive(Debug)]
 pub struct Stats{
     pub timer: Stopwatch,
     pub enemies_killed: u16,
-    pub small_powerup_used: u16,
-    pub small_powerup_collected: u16,
-    pub big_powerup_used: u16,
-    pub big_powerup_crafted: u16,
+    /// How many of each item were used, by item id
+    pub items_used: HashMap<String, u16>,
+    /// How many of each item were picked up, by item id
+    pub items_collected: HashMap<String, u16>,
+    /// How many times each recipe was crafted, by recipe id
+    pub recipes_crafted: HashMap<String, u16>,
     pub damage_taken: f32,
     pub shot_fired: u16,
     pub shot_accuracy: f32,
+    pub deaths: u16,
+}
+
+impl Stats {
+    pub fn items_used(&self, id: &str) -> u16 {
+        self.items_used.get(id).copied().unwrap_or(0)
+    }
+
+    pub fn items_collected(&self, id: &str) -> u16 {
+        self.items_collected.get(id).copied().unwrap_or(0)
+    }
+
+    pub fn recipes_crafted(&self, id: &str) -> u16 {
+        self.recipes_crafted.get(id).copied().unwrap_or(0)
+    }
+}
+
+/// The stats that go back to what they were at a checkpoint when respawning there, along with the level,
+/// so the enemies and pickups that come back can't be counted twice.
+/// The time, shots, damage and deaths keep counting.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CheckpointStats {
+    enemies_killed: u16,
+    items_used: HashMap<String, u16>,
+    items_collected: HashMap<String, u16>,
+    recipes_crafted: HashMap<String, u16>,
+}
+
+impl CheckpointStats {
+    pub fn of(stats: &Stats) -> Self {
+        Self {
+            enemies_killed: stats.enemies_killed,
+            items_used: stats.items_used.clone(),
+            items_collected: stats.items_collected.clone(),
+            recipes_crafted: stats.recipes_crafted.clone(),
+        }
+    }
+
+    fn restore(&self, stats: &mut Stats) {
+        stats.enemies_killed = self.enemies_killed;
+        stats.items_used = self.items_used.clone();
+        stats.items_collected = self.items_collected.clone();
+        stats.recipes_crafted = self.recipes_crafted.clone();
+    }
 }
 
 impl Plugin for StatsPlugin {
@@ -28,19 +78,19 @@ impl Plugin for StatsPlugin {
         app.insert_resource(Stats{
             timer: Stopwatch::new(),
             enemies_killed: 0,
-            small_powerup_used: 0,
-            small_powerup_collected: 0,
-            big_powerup_used: 0,
-            big_powerup_crafted: 0,
+            items_used: HashMap::new(),
+            items_collected: HashMap::new(),
+            recipes_crafted: HashMap::new(),
             damage_taken: 0.0,
             shot_fired: 0,
             shot_accuracy:0.0,
+            deaths: 0,
         })
-           .add_system_set(SystemSet::on_enter(GameState::Game).with_system(reset_stats))
+           .add_system_set(SystemSet::on_enter(GameState::Game).with_system(reset_stats.label("reset_stats")))
            .add_system_set(SystemSet::on_exit(GameState::Game).with_system(calculate_stats))
            .add_system_set(SystemSet::on_update(GameState::Game).with_system(update_stats))
            .add_system_set(SystemSet::on_enter(GameState::Stats).with_system(load_ui))
-           .add_system_set(SystemSet::on_update(GameState::Stats).with_system(main_menu_button))
+           .add_system_set(SystemSet::on_update(GameState::Stats).with_system(main_menu_button).with_system(watch_replay_button))
            .add_system_set(SystemSet::on_exit(GameState::Stats).with_system(drop_ui));
         
         
@@ -48,22 +98,30 @@ impl Plugin for StatsPlugin {
 }
 
 
-fn reset_stats(mut stats: ResMut<Stats>) {
-    stats.timer.reset();
+fn reset_stats(mut stats: ResMut<Stats>, checkpoints: Res<Checkpoints>) {
     stats.timer.unpause();
 
+    // Respawning continues the same run
+    if let Some(snapshot) = checkpoints.respawn_point() {
+        snapshot.stats.restore(stats.as_mut());
+        return;
+    }
+
+    stats.timer.reset();
+
     stats.enemies_killed = 0; //todo
-    stats.small_powerup_used = 0;
-    stats.small_powerup_collected = 0;
-    stats.big_powerup_used = 0;
-    stats.big_powerup_crafted = 0;
+    stats.items_used.clear();
+    stats.items_collected.clear();
+    stats.recipes_crafted.clear();
     stats.damage_taken = 0.0;
     stats.shot_fired = 0;
     stats.shot_accuracy = 0.0; 
+    stats.deaths = 0;
 }
 
-fn update_stats(mut stats: ResMut<Stats>, time: Res<Time>) {
-    stats.timer.tick(time.delta());
+fn update_stats(mut stats: ResMut<Stats>, time: Res<TimeCounter>) {
+    // The run time is measured in real time, the powerups' slow motion doesn't make it shorter
+    stats.timer.tick(time.real_delta());
 }
  
 fn calculate_stats(mut stats: ResMut<Stats>) {
@@ -76,10 +134,54 @@ fn calculate_stats(mut stats: ResMut<Stats>) {
     println!("{:?}", stats);
 }
 
-fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<RobotoFont>, stats: Res<Stats>) {
+fn load_ui(
+    mut commands: Commands,
+    paint_font: Res<PaintFont>,
+    roboto_font: Res<RobotoFont>,
+    stats: Res<Stats>,
+    highscores: Res<HighScores>,
+    new_records: Res<NewRecords>,
+    recipes: Res<RecipeRegistry>,
+    replays: Res<Replays>,
+    run_difficulty: Res<RunDifficulty>,
+) {
     let paint_font = &paint_font.0;
     let roboto_font = &roboto_font.0;
 
+    let records = highscores.level(LEVEL_ID, **run_difficulty).cloned().unwrap_or_default();
+
+    let record_lines = [
+        (
+            records.best_time.map_or_else(
+                || "Best time: -".to_string(),
+                |record| format!("Best time: {:.2}s{}", record.value, record.difficulty_suffix()),
+            ),
+            new_records.best_time,
+            "Best Time Record",
+        ),
+        (
+            records.fewest_shots.map_or_else(
+                || "Fewest shots: -".to_string(),
+                |record| format!("Fewest shots: {}{}", record.value, record.difficulty_suffix()),
+            ),
+            new_records.fewest_shots,
+            "Fewest Shots Record",
+        ),
+        (
+            records.most_kills.map_or_else(
+                || "Most kills: -".to_string(),
+                |record| format!("Most kills: {}{}", record.value, record.difficulty_suffix()),
+            ),
+            new_records.most_kills,
+            "Most Kills Record",
+        ),
+        (
+            format!("No damage runs: {}", records.no_damage_runs),
+            new_records.no_damage,
+            "No Damage Runs Record",
+        ),
+    ];
+
 	commands
 		.spawn_bundle(NodeBundle {
 			style: Style {
@@ -101,7 +203,7 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 					style: Style {
 						size: Size::new(Val::Percent(50.0), Val::Px(100.0)),
 						justify_content: JustifyContent::FlexStart,
-						flex_direction: FlexDirection::ColumnReverse,
+						flex_direction: FlexDirection::Row,
 						align_items: AlignItems::FlexStart,
 						margin: UiRect::new(Val::Px(0.0), Val::Px(0.0), Val::Px(100.0), Val::Px(0.0)),
 						..Default::default()
@@ -138,6 +240,37 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 									}
 								));
                     });
+
+                    // Only runs played from the start without respawning get recorded
+                    if replays.can_watch() {
+                        parent
+						.spawn_bundle(ButtonBundle {
+							style: Style {
+								size: Size::new(Val::Px(300.0), Val::Percent(100.0)),
+								justify_content: JustifyContent::Center,
+								align_items: AlignItems::Center,
+								margin: UiRect::new(Val::Px(20.0), Val::Px(0.0), Val::Px(0.0), Val::Px(0.0)),
+								..Default::default()
+							},
+							button: Button,
+							color: Color::RED.into(),
+							..Default::default()
+						})
+						.insert(Name::new("WatchReplayButton"))
+						.insert(WatchReplayButton)
+						.insert(ColoredButton::default())
+						.with_children(|parent| {
+							parent
+								.spawn_bundle(TextBundle::from_section(
+									"Watch Replay",
+									TextStyle {
+										font: roboto_font.clone(),
+										font_size: 32.0,
+										color: Color::BLACK
+									}
+								));
+                        });
+                    }
                 });
 
             parent.spawn_bundle(NodeBundle {
@@ -171,6 +304,25 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 				)
 				.insert(Name::new("Time"));
 
+                if replays.result() == Some(ReplayResult::OutOfSync) {
+                    parent
+					.spawn_bundle(
+						TextBundle::from_section(
+							"The replay went out of sync",
+							TextStyle {
+								font: paint_font.clone(),
+								font_size: 32.0,
+								color: Color::RED,
+							},
+						)
+						.with_style(Style {
+							margin: UiRect::all(Val::Px(5.0)),
+							..default()
+						}),
+					)
+					.insert(Name::new("Replay Out Of Sync"));
+                }
+
                 parent
 				.spawn_bundle(
 					TextBundle::from_section(
@@ -205,6 +357,23 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 				)
 				.insert(Name::new("Damage Taken"));
 
+                parent
+				.spawn_bundle(
+					TextBundle::from_section(
+						format!("Deaths: {}", stats.deaths),
+						TextStyle {
+							font: paint_font.clone(),
+							font_size: 32.0,
+							color: Color::WHITE,
+						},
+					)
+					.with_style(Style {
+						margin: UiRect::all(Val::Px(5.0)),
+						..default()
+					}),
+				)
+				.insert(Name::new("Deaths"));
+
                 parent
 				.spawn_bundle(
 					TextBundle::from_section(
@@ -242,7 +411,7 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
                 parent
 				.spawn_bundle(
 					TextBundle::from_section(
-						format!("Cocaine snorted: {}", stats.small_powerup_used),
+						format!("Cocaine snorted: {}", stats.items_used("cocaine")),
 						TextStyle {
 							font: paint_font.clone(),
 							font_size: 32.0,
@@ -259,7 +428,7 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
                 parent
 				.spawn_bundle(
 					TextBundle::from_section(
-						format!("Cocaine stolen: {}", stats.small_powerup_collected),
+						format!("Cocaine stolen: {}", stats.items_collected("cocaine")),
 						TextStyle {
 							font: paint_font.clone(),
 							font_size: 32.0,
@@ -276,7 +445,7 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
                 parent
 				.spawn_bundle(
 					TextBundle::from_section(
-						format!("Fun Dust administered: {}", stats.big_powerup_used),
+						format!("Fun Dust administered: {}", stats.items_used("fun_dust")),
 						TextStyle {
 							font: paint_font.clone(),
 							font_size: 32.0,
@@ -290,22 +459,43 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 				)
 				.insert(Name::new("Big Power Ups Used"));
 
-                parent
-				.spawn_bundle(
-					TextBundle::from_section(
-						format!("Fun Dust crafted: {}", stats.big_powerup_crafted),
-						TextStyle {
-							font: paint_font.clone(),
-							font_size: 32.0,
-							color: Color::WHITE,
-						},
-					)
-					.with_style(Style {
-						margin: UiRect::all(Val::Px(5.0)),
-						..default()
-					}),
-				)
-				.insert(Name::new("Big Power Ups Crafted"));
+                for recipe in recipes.iter() {
+                    parent
+                    .spawn_bundle(
+                        TextBundle::from_section(
+                            format!("{} crafted: {}", recipe.name, stats.recipes_crafted(&recipe.id)),
+                            TextStyle {
+                                font: paint_font.clone(),
+                                font_size: 32.0,
+                                color: Color::WHITE,
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(5.0)),
+                            ..default()
+                        }),
+                    )
+                    .insert(Name::new(format!("{} Crafted", recipe.name)));
+                }
+
+                for (text, is_new, name) in record_lines {
+                    parent
+                    .spawn_bundle(
+                        TextBundle::from_section(
+                            if is_new { format!("{} (new record!)", text) } else { text },
+                            TextStyle {
+                                font: paint_font.clone(),
+                                font_size: 32.0,
+                                color: if is_new { RECORD_COLOR } else { Color::WHITE },
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(5.0)),
+                            ..default()
+                        }),
+                    )
+                    .insert(Name::new(name));
+                }
             });
         });
 }
@@ -327,4 +517,23 @@ fn main_menu_button(
 			state.set(GameState::MainMenu).expect("Failed to change state!");
 		}
 	}
-}
\ No newline at end of file
+}
+
+fn watch_replay_button(
+	mut interaction_query: Query<
+		&Interaction,
+		(Changed<Interaction>, With<WatchReplayButton>)
+	>,
+	mut state: ResMut<State<GameState>>,
+	mut replays: ResMut<Replays>,
+	mut settings: ResMut<Settings>,
+	mut rng: ResMut<GameRng>,
+	viewport: Res<Viewport>,
+) {
+	for interaction in &mut interaction_query {
+		if *interaction == Interaction::Clicked {
+			replays.watch(settings.as_mut(), rng.as_mut(), viewport.as_ref());
+			state.set(GameState::Game).expect("Failed to change state!");
+		}
+	}
+}
@@ -0,0 +1,84 @@
+//! Persistent key-value storage for things like saves.
+//! Native builds keep every entry in its own file, the web build uses the browser's local storage.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, path::PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+const STORAGE_DIRECTORY: &str = "./saves";
+
+/// Where the entries are kept, a resource so every headless simulation can keep them somewhere else.
+#[derive(Clone)]
+pub struct Storage {
+	#[cfg(not(target_arch = "wasm32"))]
+	directory: PathBuf,
+}
+
+impl Default for Storage {
+	fn default() -> Self {
+		Self {
+			#[cfg(not(target_arch = "wasm32"))]
+			directory: PathBuf::from(STORAGE_DIRECTORY),
+		}
+	}
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage {
+	/// An empty directory of its own for every test, so they don't mess with the saves of the actual game
+	/// or with each other.
+	#[cfg(test)]
+	pub fn for_test(name: &str) -> Self {
+		let directory = std::env::temp_dir()
+			.join("crackhead-adventures-tests")
+			.join(format!("{}-{}", std::process::id(), name.replace("::", "-")));
+
+		// Left over from an earlier run
+		let _ = fs::remove_dir_all(&directory);
+
+		Self { directory }
+	}
+
+	fn entry_path(&self, key: &str) -> PathBuf {
+		self.directory.join(format!("{}.ron", key))
+	}
+
+	pub fn read(&self, key: &str) -> Option<String> {
+		fs::read_to_string(self.entry_path(key)).ok()
+	}
+
+	pub fn write(&self, key: &str, contents: &str) -> Result<(), String> {
+		fs::create_dir_all(&self.directory).map_err(|error| error.to_string())?;
+		fs::write(self.entry_path(key), contents).map_err(|error| error.to_string())
+	}
+
+	pub fn remove(&self, key: &str) {
+		// It's fine if there was nothing to remove
+		let _ = fs::remove_file(self.entry_path(key));
+	}
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+	web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage {
+	pub fn read(&self, key: &str) -> Option<String> {
+		local_storage()?.get_item(key).ok()?
+	}
+
+	pub fn write(&self, key: &str, contents: &str) -> Result<(), String> {
+		local_storage()
+			.ok_or_else(|| "Local storage is not available".to_string())?
+			.set_item(key, contents)
+			.map_err(|_| format!("Failed to write \"{}\" to the local storage", key))
+	}
+
+	pub fn remove(&self, key: &str) {
+		if let Some(storage) = local_storage() {
+			let _ = storage.remove_item(key);
+		}
+	}
+}
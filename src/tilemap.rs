@@ -4,26 +4,62 @@ use std::path::{PathBuf, Path};
 
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use tiled::{Chunk, LayerType, Loader, TileLayer, Tileset, Map, ResourceReader, DefaultResourceCache};
+use tiled::{Chunk, LayerType, Loader, PropertyValue, TileLayer, Tileset, Map, ResourceReader, DefaultResourceCache};
 
-use crate::cocaine::CocaineBundle;
+use crate::checkpoint::{CheckpointBundle, Checkpoints};
+use crate::crafting::CraftingStation;
 use crate::enemy::EnemyBundle;
 use crate::enemy_nav_mesh::EnemyNavMesh;
+use crate::item::COCAINE;
+use crate::lighting::PointLight2d;
+use crate::pickup::{Pickup, PickupBundle};
 use crate::player::PlayerBundle;
 use crate::win::{WinBundle, WinMaterial};
+use crate::shader_params::ShaderTuning;
 use crate::{GameState, TILE_SIZE};
 
+/// The name of the level, used to tell records of different levels apart.
+pub const LEVEL_ID: &str = "level";
+
 #[derive(Component)]
 pub struct Tilemap;
 
 #[derive(Component)]
 pub struct TileCollider;
 
+/// The position of the tile an entity was placed on in the level file.
+/// Unlike the entity itself, it stays the same when the level gets reloaded.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TilePosition(pub IVec2);
+
+/// The area covered by the tiles of the level, the camera never shows anything outside of it.
+pub struct LevelBounds {
+	pub min: Vec2,
+	pub max: Vec2,
+}
+
+impl LevelBounds {
+	fn include(&mut self, tile_position: Vec2) {
+		self.min = self.min.min(tile_position - TILE_SIZE / 2.0);
+		self.max = self.max.max(tile_position + TILE_SIZE / 2.0);
+	}
+}
+
+/// The contents of the level file that gets loaded, the tests swap it for a smaller level.
+pub struct LevelSource(pub &'static [u8]);
+
+impl Default for LevelSource {
+	fn default() -> Self {
+		Self(include_bytes!("../assets/level/level.tmx"))
+	}
+}
+
 pub struct TileMapPlugin;
 
 impl Plugin for TileMapPlugin {
 	fn build(&self, app: &mut App) {
 		app.insert_resource(EnemyNavMesh::new())
+			.init_resource::<LevelSource>()
 			.insert_resource(TexturesMemo {
 				memoized: HashMap::new(),
 			})
@@ -140,8 +176,10 @@ impl TexturesMemo {
 	}
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-struct WasmResourceReader;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WasmResourceReader {
+	level: &'static [u8],
+}
 
 impl ResourceReader for WasmResourceReader {
 	type Resource = &'static [u8];
@@ -150,7 +188,7 @@ impl ResourceReader for WasmResourceReader {
 	fn read_from(&mut self, path: &Path) -> std::result::Result<Self::Resource, Self::Error> {
 		match path.to_str().expect("Given path is not a valid unicode string") {
 			"level.tmx" => {
-				Ok(&include_bytes!("../assets/level/level.tmx")[..])
+				Ok(self.level)
 			},
 			"tileset.tsx" => {
 				Ok(&include_bytes!("../assets/level/tileset.tsx")[..])
@@ -166,8 +204,11 @@ trait SimpleNew {
 	fn new() -> Self;
 }
 
-fn load_tilemap() -> (Map, Tileset) {
-	let mut loader = Loader::<DefaultResourceCache, WasmResourceReader>::new();
+fn load_tilemap(level: &LevelSource) -> (Map, Tileset) {
+	let mut loader = Loader::with_cache_and_reader(
+		DefaultResourceCache::new(),
+		WasmResourceReader { level: level.0 },
+	);
 
 	(
 		loader.load_tmx_map("level.tmx").unwrap(),
@@ -181,13 +222,23 @@ fn load_level(
 	mut textures: ResMut<TexturesMemo>,
 	mut nav_mesh: ResMut<EnemyNavMesh>,
 	mut win_materials: ResMut<Assets<WinMaterial>>,
+	tuning: Res<ShaderTuning>,
+	checkpoints: Res<Checkpoints>,
+	level: Res<LevelSource>,
 ) {
-	let (map, tileset) = load_tilemap();
+	let (map, tileset) = load_tilemap(&level);
+
+	let respawn_point = checkpoints.respawn_point();
 
 	let layers = map.layers();
 
 	let mut entities = Vec::new();
 
+	let mut bounds = LevelBounds {
+		min: Vec2::splat(f32::MAX),
+		max: Vec2::splat(f32::MIN),
+	};
+
 	let is_wall_at = |x: i32, y: i32| {
 		let wall_layer = map
 			.get_layer(1)
@@ -232,11 +283,18 @@ fn load_level(
 							for x in 0..Chunk::WIDTH as i32 {
 								for y in 0..Chunk::HEIGHT as i32 {
 									if let Some(tile) = chunk.get_tile_data(x, y) {
+										let tile_coords = IVec2::new(
+											chunk_pos.0 * Chunk::WIDTH as i32 + x,
+											chunk_pos.1 * Chunk::HEIGHT as i32 + y,
+										);
+
 										let tile_pos = Vec2::new(
-											(chunk_pos.0 * Chunk::WIDTH as i32 + x) as f32,
-											-(chunk_pos.1 * Chunk::HEIGHT as i32 + y) as f32,
+											tile_coords.x as f32,
+											-tile_coords.y as f32,
 										) * TILE_SIZE;
 
+										bounds.include(tile_pos);
+
 										let (flip_x, flip_y) = (
 											tile.flip_h || tile.flip_d,
 											tile.flip_v || tile.flip_d,
@@ -289,37 +347,53 @@ fn load_level(
 													}
 													2 => {
 														// Player layer
-														commands.spawn_bundle(PlayerBundle::spawn(
-															tile_pos,
+														let mut player = commands.spawn_bundle(PlayerBundle::spawn(
+															respawn_point.map_or(tile_pos, |snapshot| snapshot.position),
 															textures
 																.get(&image_source, &asset_server),
 															flip_x,
 															flip_y,
-														)).id()
+														));
+
+														if let Some(snapshot) = respawn_point {
+															player
+																.insert(snapshot.health.clone())
+																.insert(snapshot.inventory.clone());
+														}
+
+														player.id()
 													}
 													3 => {
 														// Enemy layer
+														if respawn_point.map_or(false, |snapshot| snapshot.killed_enemies.contains(&tile_coords)) {
+															continue;
+														}
+
 														commands.spawn_bundle(EnemyBundle::spawn(
 															tile_pos,
 															textures
 																.get(&image_source, &asset_server),
 															flip_x,
 															flip_y,
-														)).id()
+														)).insert(TilePosition(tile_coords)).id()
 													}
 													4 => {
 														// Cocaine layer
-														commands.spawn_bundle(CocaineBundle::spawn(
+														if respawn_point.map_or(false, |snapshot| snapshot.collected_pickups.contains(&tile_coords)) {
+															continue;
+														}
+
+														commands.spawn_bundle(PickupBundle::spawn(
 															tile_pos,
 															textures
 																.get(&image_source, &asset_server),
 															flip_x,
 															flip_y,
-														)).id()
+														).with_pickup(Pickup::item(COCAINE, 1))).insert(TilePosition(tile_coords)).id()
 													}
 													5 => {
 														// Details layer
-														commands.spawn_bundle(SpriteBundle {
+														let mut detail = commands.spawn_bundle(SpriteBundle {
 															transform: Transform::from_translation(
 																tile_pos.extend(20.0),
 															),
@@ -331,7 +405,19 @@ fn load_level(
 															texture: textures
 																.get(&image_source, &asset_server),
 															..Default::default()
-														}).id()
+														});
+
+														// Tiles with a station property in the tileset can be crafted at
+														if let Some(PropertyValue::StringValue(station)) = tile.properties.get("station") {
+															detail.insert(CraftingStation(station.clone()));
+														}
+
+														// And the ones with a light radius are lamps
+														if let Some(light) = PointLight2d::from_properties(&tile.properties) {
+															detail.insert(light);
+														}
+
+														detail.id()
 													}
 													6 => {
 														// Win layer
@@ -341,7 +427,7 @@ fn load_level(
 																	&image_source,
 																	&asset_server,
 																),
-																time: 0,
+																params: tuning.win,
 															});
 
 														commands.spawn_bundle(WinBundle::spawn(
@@ -352,6 +438,34 @@ fn load_level(
 															flip_y,
 														)).insert(material).id()
 													}
+													7 => {
+														// Checkpoint layer
+														commands.spawn_bundle(CheckpointBundle::spawn(
+															tile_pos,
+															textures
+																.get(&image_source, &asset_server),
+															flip_x,
+															flip_y,
+														)).insert(TilePosition(tile_coords)).id()
+													}
+													8 => {
+														// Pickup layer
+														if respawn_point.map_or(false, |snapshot| snapshot.collected_pickups.contains(&tile_coords)) {
+															continue;
+														}
+
+														// What the pickup gives is set with properties in the tileset
+														let pickup = Pickup::from_properties(&tile.properties)
+															.expect("Tile on the pickup layer without pickup properties!");
+
+														commands.spawn_bundle(PickupBundle::spawn(
+															tile_pos,
+															textures
+																.get(&image_source, &asset_server),
+															flip_x,
+															flip_y,
+														).with_pickup(pickup)).insert(TilePosition(tile_coords)).id()
+													}
 													_ => {
 														panic!("Too much layers in the level file");
 													}
@@ -371,6 +485,8 @@ fn load_level(
 
 	nav_mesh.bake();
 
+	commands.insert_resource(bounds);
+
 	commands
 		.spawn()
 		.insert(Name::new("Tilemap"))
@@ -1,38 +1,180 @@
 use std::time::Duration;
 
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 
+/// How fast the layers move towards their target scale, in scale per real second.
+const TRANSITION_SPEED: f32 = 4.0;
+
+/// Everything that can change how fast the game runs.
+/// The scales of all the layers get multiplied together, so they can't overwrite each other.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TimeLayer {
+	/// Slow motion from the powerup effects
+	Powerup,
+	/// The pause menu
+	Pause,
+	/// Freezes the game for a moment when something hits hard
+	HitStop,
+}
+
+struct ScaleLayer {
+	current: f32,
+	target: f32,
+	/// Real seconds left until the layer removes itself
+	remaining: Option<f32>,
+}
+
+/// The clock everything should read time from.
+///
+/// Game time is scaled by the layers and stops while the game is paused,
+/// use it for everything that happens in the game.
+/// Real time keeps going no matter what, use it for menus and other things outside of the game.
 pub struct TimeCounter {
-	pub timescale: f32,
+	layers: HashMap<TimeLayer, ScaleLayer>,
+	timescale: f32,
 	delta: f32,
 	seconds_since_startup: f32,
+	real_delta: f32,
+	real_seconds_since_startup: f32,
 }
 
 impl TimeCounter {
 	pub fn new() -> Self {
 		Self {
+			layers: HashMap::new(),
 			timescale: 1.0,
 			delta: 0.0,
 			seconds_since_startup: 0.0,
+			real_delta: 0.0,
+			real_seconds_since_startup: 0.0,
 		}
 	}
 
-	pub fn step(&mut self, delta: f32) {
-		self.delta = delta;
-		self.seconds_since_startup += delta;
+	pub fn step(&mut self, real_delta: f32) {
+		self.real_delta = real_delta;
+		self.real_seconds_since_startup += real_delta;
+
+		for layer in self.layers.values_mut() {
+			let difference = layer.target - layer.current;
+			let step = TRANSITION_SPEED * real_delta;
+
+			layer.current = if difference.abs() <= step {
+				layer.target
+			} else {
+				layer.current + step * difference.signum()
+			};
+
+			if let Some(remaining) = &mut layer.remaining {
+				*remaining -= real_delta;
+			}
+		}
+
+		self.layers
+			.retain(|_, layer| layer.remaining.map_or(true, |remaining| remaining > 0.0));
+
+		self.timescale = self.layers.values().map(|layer| layer.current).product();
+
+		self.delta = self.timescale * real_delta;
+		self.seconds_since_startup += self.delta;
+	}
+
+	/// Moves the scale of the layer smoothly towards the given one.
+	pub fn set_scale(&mut self, layer: TimeLayer, scale: f32) {
+		let layer = self.layers.entry(layer).or_insert(ScaleLayer {
+			current: 1.0,
+			target: 1.0,
+			remaining: None,
+		});
+
+		layer.target = scale.max(0.0);
+	}
+
+	/// Sets the scale of the layer without a transition.
+	pub fn set_scale_immediately(&mut self, layer: TimeLayer, scale: f32) {
+		let scale = scale.max(0.0);
+
+		self.layers.insert(
+			layer,
+			ScaleLayer {
+				current: scale,
+				target: scale,
+				remaining: None,
+			},
+		);
+	}
+
+	/// Sets the scale of the layer without a transition, for the given number of real seconds.
+	pub fn set_scale_for(&mut self, layer: TimeLayer, scale: f32, duration: f32) {
+		self.set_scale_immediately(layer, scale);
+
+		if let Some(layer) = self.layers.get_mut(&layer) {
+			layer.remaining = Some(duration);
+		}
+	}
+
+	/// Removes the layer right away, so it doesn't affect the timescale anymore.
+	pub fn clear_scale(&mut self, layer: TimeLayer) {
+		self.layers.remove(&layer);
+	}
+
+	/// All the layers multiplied together.
+	pub fn timescale(&self) -> f32 {
+		self.timescale
 	}
 
+	/// Game time since the last frame.
 	pub fn delta(&self) -> Duration {
-		Duration::from_millis((self.delta_seconds() * 1000.0) as u64)
+		Duration::from_secs_f32(self.delta)
 	}
 
+	/// Game time since the last frame.
 	pub fn delta_seconds(&self) -> f32 {
-		self.timescale * self.delta
+		self.delta
 	}
 
+	/// Game time since the game started.
 	pub fn seconds_since_startup(&self) -> f32 {
 		self.seconds_since_startup
 	}
+
+	/// Real time since the last frame.
+	pub fn real_delta(&self) -> Duration {
+		Duration::from_secs_f32(self.real_delta)
+	}
+
+	/// Real time since the last frame.
+	pub fn real_delta_seconds(&self) -> f32 {
+		self.real_delta
+	}
+
+	/// Real time since the game started.
+	pub fn real_seconds_since_startup(&self) -> f32 {
+		self.real_seconds_since_startup
+	}
+
+	/// Overwrites the time of the current frame with the recorded one, so a replay runs exactly like the run it recorded.
+	pub fn replay_frame(&mut self, delta: f32, real_delta: f32) {
+		self.seconds_since_startup += delta - self.delta;
+		self.real_seconds_since_startup += real_delta - self.real_delta;
+		self.delta = delta;
+		self.real_delta = real_delta;
+	}
+
+	/// Stops the game time until `resume` is called.
+	pub fn pause(&mut self) {
+		self.set_scale_immediately(TimeLayer::Pause, 0.0);
+		self.timescale = 0.0;
+		self.delta = 0.0;
+	}
+
+	pub fn resume(&mut self) {
+		self.clear_scale(TimeLayer::Pause);
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.layers.contains_key(&TimeLayer::Pause)
+	}
 }
 
 pub struct TimePlugin;
@@ -42,7 +184,7 @@ impl Plugin for TimePlugin {
 		app.add_startup_system(register_time)
 			.add_system_set_to_stage(
 				CoreStage::PreUpdate,
-				SystemSet::new().with_system(update_time),
+				SystemSet::new().with_system(update_time.label("update_time")),
 			);
 	}
 }
@@ -51,6 +193,6 @@ fn register_time(mut commands: Commands) {
 	commands.insert_resource(TimeCounter::new());
 }
 
-fn update_time(mut time: ResMut<TimeCounter>, game_time: Res<Time>) {
-	time.step(game_time.delta_seconds());
+fn update_time(mut time: ResMut<TimeCounter>, real_time: Res<Time>) {
+	time.step(real_time.delta_seconds());
 }
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
+use bevy::reflect::FromReflect;
 use bevy_inspector_egui::prelude::*;
-
-use crate::time::TimeCounter;
+use serde::{Deserialize, Serialize};
 
 #[derive(Default, Reflect, Inspectable, Component)]
 #[reflect(Component)]
@@ -16,32 +16,65 @@ pub struct Shooting {
 
 pub struct ShootEvent(pub Vec2);
 
-#[derive(Default, Reflect, Inspectable, Component)]
+/// Sent whenever a unit takes damage, everything that reacts to getting hurt listens to this.
+pub struct DamageEvent {
+	pub target: Entity,
+	pub amount: f32,
+	/// Where the attacker was
+	pub source: Vec2,
+}
+
+#[derive(Default, Clone, Reflect, Inspectable, Component, Serialize, Deserialize)]
 #[reflect(Component)]
 pub struct Health {
 	health: f32,
 	max_health: f32,
+	/// Takes most of the damage before the health does
+	armor: f32,
+	max_armor: f32,
 }
 
+/// How much of the damage the armor takes while there's any left
+const ARMOR_ABSORPTION: f32 = 0.75;
+
 impl Health {
 	pub fn new(max_health: f32) -> Self {
 		Self {
 			health: max_health,
 			max_health,
+			armor: 0.0,
+			max_armor: 0.0,
 		}
 	}
 
+	pub fn with_max_armor(mut self, max_armor: f32) -> Self {
+		self.max_armor = max_armor;
+		self
+	}
+
+	/// Damage from getting hit, mostly soaked up by the armor.
 	/// # Returns
 	/// True if the health reached zero.
 	#[warn(unused_must_use)]
 	#[must_use]
 	pub fn take_damage(&mut self, amount: f32) -> bool {
+		let absorbed = (amount * ARMOR_ABSORPTION).min(self.armor);
+		self.armor -= absorbed;
+
+		self.lose_health(amount - absorbed)
+	}
+
+	/// Damage the armor can't stop, like the drugs wearing off.
+	/// # Returns
+	/// True if the health reached zero.
+	#[warn(unused_must_use)]
+	#[must_use]
+	pub fn lose_health(&mut self, amount: f32) -> bool {
 		self.health -= amount;
 
 		self.health <= 0.0
 	}
 
-	#[allow(unused)]
 	pub fn heal(&mut self, amount: f32) {
 		self.health += amount;
 
@@ -57,64 +90,152 @@ impl Health {
 	pub fn get_max_health(&self) -> f32 {
 		self.max_health
 	}
+
+	/// Health and armor together, goes down whenever the unit gets hurt.
+	pub fn total(&self) -> f32 {
+		self.health + self.armor
+	}
+
+	pub fn add_armor(&mut self, amount: f32) {
+		self.armor = (self.armor + amount).min(self.max_armor);
+	}
+
+	pub fn get_armor(&self) -> f32 {
+		self.armor
+	}
+
+	pub fn get_max_armor(&self) -> f32 {
+		self.max_armor
+	}
 	#[allow(unused)]
 	pub fn set_health(&mut self, hp: f32) {
 		self.health = hp;
 	}
 }
 
-pub trait Effect {
-	fn apply(&self, movement: &mut Movement, health: &mut Health, shooting: &mut Shooting, time: &mut TimeCounter);
-	fn finish(&self, movement: &mut Movement, health: &mut Health, shooting: &mut Shooting, time: &mut TimeCounter);
+/// The stats of a unit before any modifiers are applied.
+#[derive(Component, Clone)]
+pub struct BaseStats {
+	pub speed: f32,
+	pub weapon_cooldown: f32,
 }
 
-#[derive(Default, Reflect, Inspectable, Component)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Stat {
+	MovementSpeed,
+	WeaponCooldown,
+	Timescale,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Modifier {
+	Add(f32),
+	Multiply(f32),
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct StatModifier {
+	pub stat: Stat,
+	pub modifier: Modifier,
+}
+
+impl StatModifier {
+	/// Weakens or strengthens the modifier, a potency of 0 makes it do nothing.
+	pub fn with_potency(&self, potency: f32) -> Self {
+		let modifier = match self.modifier {
+			Modifier::Add(amount) => Modifier::Add(amount * potency),
+			Modifier::Multiply(factor) => Modifier::Multiply(1.0 + (factor - 1.0) * potency),
+		};
+
+		Self { stat: self.stat, modifier }
+	}
+}
+
+/// Computes the value of a stat with all the given modifiers applied.
+/// All the additions are applied first, then the sum gets multiplied, so the order of the modifiers doesn't matter.
+pub fn modified_stat(base: f32, stat: Stat, modifiers: impl IntoIterator<Item = StatModifier>) -> f32 {
+	let (added, multiplier) = modifiers
+		.into_iter()
+		.filter(|modifier| modifier.stat == stat)
+		.fold((0.0, 1.0), |(added, multiplier), modifier| match modifier.modifier {
+			Modifier::Add(amount) => (added + amount, multiplier),
+			Modifier::Multiply(factor) => (added, multiplier * factor),
+		});
+
+	(base + added) * multiplier
+}
+
+#[derive(Default, Clone, Reflect, FromReflect, Inspectable, Serialize, Deserialize)]
+pub struct ItemStack {
+	pub id: String,
+	pub quantity: u32,
+}
+
+/// Items carried by a unit, kept in the order they were first picked up in.
+#[derive(Default, Clone, Reflect, Inspectable, Component, Serialize, Deserialize)]
 #[reflect(Component)]
 pub struct Inventory {
-	small_powerups: usize,
-	big_powerups: usize,
+	items: Vec<ItemStack>,
 }
 
-#[allow(dead_code)]
 impl Inventory {
-	pub fn new() -> Self {
+	pub fn with_items<'a>(items: impl IntoIterator<Item = (&'a str, u32)>) -> Self {
 		Self {
-			small_powerups: 1,
-			big_powerups: 0,
+			items: items
+				.into_iter()
+				.map(|(id, quantity)| ItemStack {
+					id: id.to_string(),
+					quantity,
+				})
+				.collect(),
 		}
 	}
 
-	pub fn get_small_powerup_quantity(&self) -> usize {
-		self.small_powerups
+	pub fn quantity(&self, id: &str) -> u32 {
+		self.items
+			.iter()
+			.find(|stack| stack.id == id)
+			.map_or(0, |stack| stack.quantity)
 	}
 
-	pub fn subtract_small_powerup(&mut self, amount: usize) -> bool {
-		if amount > self.small_powerups {
-			false
-		} else {
-			self.small_powerups -= amount;
-			true
+	/// Adds as many of the items as fit under the max stack.
+	///
+	/// # Returns
+	/// How many of them were added.
+	pub fn add(&mut self, id: &str, amount: u32, max_stack: u32) -> u32 {
+		if let Some(stack) = self.items.iter_mut().find(|stack| stack.id == id) {
+			let added = amount.min(max_stack.saturating_sub(stack.quantity));
+			stack.quantity += added;
+
+			return added;
 		}
-	}
 
-	pub fn add_small_powerup(&mut self, amount: usize) {
-		self.small_powerups += amount;
-	}
+		let added = amount.min(max_stack);
 
-	pub fn get_big_powerup_quantity(&self) -> usize {
-		self.big_powerups
+		// An empty stack would still show up in the HUD and the saves
+		if added > 0 {
+			self.items.push(ItemStack {
+				id: id.to_string(),
+				quantity: added,
+			});
+		}
+
+		added
 	}
 
-	pub fn subtract_big_powerup(&mut self, amount: usize) -> bool {
-		if amount > self.big_powerups {
-			false
-		} else {
-			self.big_powerups -= amount;
-			true
+	/// # Returns
+	/// False if there weren't enough of the items, in which case none get removed.
+	pub fn remove(&mut self, id: &str, amount: u32) -> bool {
+		match self.items.iter_mut().find(|stack| stack.id == id) {
+			Some(stack) if stack.quantity >= amount => {
+				stack.quantity -= amount;
+				true
+			}
+			_ => amount == 0,
 		}
 	}
 
-	pub fn add_big_powerup(&mut self, amount: usize) {
-		self.big_powerups += amount;
+	pub fn items(&self) -> &[ItemStack] {
+		&self.items
 	}
 }
@@ -3,8 +3,12 @@ use bevy::{prelude::*, reflect::TypeUuid, render::render_resource::{AsBindGroup,
 use crate::{
 	button::ColoredButton,
 	fonts::{PaintFont, RobotoFont},
-	tilemap::Tile,
+	tilemap::{Tile, LEVEL_ID},
 	GameState, stats::Stats,
+	time::TimeCounter,
+	shader_params::{ShaderParams, ShaderTuning},
+	highscores::{HighScores, NewRecords, RECORD_COLOR},
+	difficulty::RunDifficulty,
 };
 
 #[derive(Component, Default)]
@@ -26,7 +30,7 @@ pub struct WinPlugin;
 
 impl Plugin for WinPlugin {
 	fn build(&self, app: &mut App) {
-		app.add_system_set(SystemSet::on_enter(GameState::Win).with_system(load_ui))
+		app.add_system_set(SystemSet::on_enter(GameState::Win).with_system(load_ui.after("record_run")))
 		.add_plugin(Material2dPlugin::<WinMaterial>::default())
 			.add_system_set(
 				SystemSet::on_update(GameState::Win)
@@ -73,7 +77,7 @@ pub struct WinMaterial {
 	#[sampler(1)]
 	pub source_image: Handle<Image>,
 	#[uniform(2)]
-	pub time: u32,
+	pub params: ShaderParams,
 }
 
 impl Material2d for WinMaterial {
@@ -82,16 +86,30 @@ impl Material2d for WinMaterial {
 	}
 }
 
-fn update_win_material(win: Query<(&Handle<WinMaterial>, &Handle<Image>)>, time: Res<Time>, mut win_materials: ResMut<Assets<WinMaterial>>) {
+fn update_win_material(win: Query<(&Handle<WinMaterial>, &Handle<Image>)>, time: Res<TimeCounter>, tuning: Res<ShaderTuning>, mut win_materials: ResMut<Assets<WinMaterial>>) {
 	for (win_material, texture) in win.iter() {
 		let mut win_material = win_materials.get_mut(win_material).unwrap();
 
 		win_material.source_image = texture.clone();
-		win_material.time = (time.seconds_since_startup() * 1000.0).floor() as u32;
+		win_material.params = tuning.win.at(time.real_seconds_since_startup());
 	}
 }
 
-fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<RobotoFont>, stats: Res<Stats>) {
+fn load_ui(
+	mut commands: Commands,
+	paint_font: Res<PaintFont>,
+	roboto_font: Res<RobotoFont>,
+	stats: Res<Stats>,
+	highscores: Res<HighScores>,
+	new_records: Res<NewRecords>,
+	run_difficulty: Res<RunDifficulty>,
+) {
+	let best_time = highscores
+		.level(LEVEL_ID, **run_difficulty)
+		.and_then(|records| records.best_time)
+		.map(|best_time| format!("{:.2}s{}", best_time.value, best_time.difficulty_suffix()))
+		.unwrap_or_else(|| format!("{:.2}s", stats.timer.elapsed_secs()));
+
 	commands
 		.spawn_bundle(NodeBundle {
 			style: Style {
@@ -158,6 +176,46 @@ fn load_ui(mut commands: Commands, paint_font: Res<PaintFont>, roboto_font: Res<
 				)
 				.insert(Name::new("Time"));
 
+			parent
+				.spawn_bundle(
+					TextBundle::from_section(
+						if new_records.best_time {
+							"New record!".to_string()
+						} else {
+							format!("Best time: {}", best_time)
+						},
+						TextStyle {
+							font: paint_font.0.clone(),
+							font_size: 32.0,
+							color: if new_records.best_time { RECORD_COLOR } else { Color::WHITE },
+						},
+					)
+					.with_style(Style {
+						margin: UiRect::all(Val::Px(5.0)),
+						..default()
+					}),
+				)
+				.insert(Name::new("BestTime"));
+
+			if new_records.any() && !new_records.best_time {
+				parent
+					.spawn_bundle(
+						TextBundle::from_section(
+							"You set a new record, check your stats!",
+							TextStyle {
+								font: paint_font.0.clone(),
+								font_size: 32.0,
+								color: RECORD_COLOR,
+							},
+						)
+						.with_style(Style {
+							margin: UiRect::all(Val::Px(5.0)),
+							..default()
+						}),
+					)
+					.insert(Name::new("NewRecords"));
+			}
+
 			parent
 				.spawn_bundle(NodeBundle {
 					style: Style {